@@ -1,22 +1,40 @@
-// use std::borrow::{Borrow, BorrowMut};
-// use std::borrow::{Borrow, BorrowMut};
-// use std::any::Any;
 use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
-// use std::env;
-use std::ffi::CString;
-// use std::fs;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
 use std::iter::zip;
-// use std::ops::{Deref, DerefMut};
+use std::ops::{Deref, DerefMut};
 use std::rc::Rc;
 
-// use self::Form::*;
-
 pub mod builtin;
+pub mod error;
+pub mod keyword;
+pub mod macros;
+pub mod map;
+pub mod number;
+pub mod pair;
+pub mod printer;
+pub mod set;
+pub mod span;
 pub mod special;
-use builtin::builtin_function;
-
-use pest::error::Error;
+pub mod string;
+pub mod vector;
+use builtin::{builtin_function, list_or_nil};
+pub use error::{HanaError, HanaResult};
+use keyword::{call_keyword, Keyword};
+use map::{evaluate_map, Map};
+use number::Number;
+use pair::Cons;
+use set::{evaluate_set, Set};
+pub use span::{Source, Span};
+use special::tail_special_form;
+use vector::{evaluate_vector, Vector};
+
+use indexmap::{IndexMap, IndexSet};
+use pest::error::{Error as PestError, ErrorVariant};
+use pest::iterators::Pair;
 use pest::Parser;
 use pest_derive::Parser;
 
@@ -24,23 +42,39 @@ use pest_derive::Parser;
 #[grammar = "hana.pest"]
 pub struct HanaParser;
 
+// How deeply evaluation may nest by default before raising a stack overflow error.
+pub const DEFAULT_MAX_DEPTH: usize = 1000;
+
 pub type Integer = i32;
+pub type BigInt = num_bigint::BigInt;
+pub type Ratio = num_rational::BigRational;
 pub type Real = f64;
-pub type Str = CString;
+pub type Str = Rc<str>;
 pub type Symbol = String;
 
 /*
     In Hana, a form is any valid data that can be evaluated by the evaluator.
+    Symbols that were read from source text remember where they came from, the
+    same way lists do, so that an unbound symbol can be reported at its location.
 */
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub enum Form {
     Integer(Integer),
+    BigInt(BigInt),
+    Ratio(Ratio),
     Real(Real),
     Str(Str),
+    Char(char),
     Bool(bool),
-    Symbol(Symbol),
+    Symbol(Symbol, Option<Span>),
+    Keyword(Keyword),
     List(List),
+    Pair(Rc<Cons>),
+    Map(Rc<Map>),
+    Set(Rc<Set>),
+    Vector(Vector),
     Function(Function),
+    Macro(Function),
     Nil(),
 }
 
@@ -50,109 +84,594 @@ impl Default for Form {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub struct List {
-    pub elements: Vec<Box<Form>>,
-}
+/*
+    Forms are equal when they have the same structure. Unlike with '=', a nan is
+    equal to any other nan here, or a map with a nan key could never be looked up.
 
-#[derive(Debug, Clone, PartialEq)]
-pub struct Function {
-    pub params: Vec<Form>,
-    pub context: Context,
-    pub env: Environment,
-    pub body: Box<Form>,
+    Rather than recursing into each element, the elements still to be compared are
+    kept on a stack of their own, so that deeply nested forms can't overflow the stack.
+*/
+impl PartialEq for Form {
+    fn eq(&self, other: &Self) -> bool {
+        let mut pending = vec![];
+        if !shallow_eq(self, other, &mut pending) {
+            return false;
+        }
+
+        while let Some((lhs, rhs)) = pending.pop() {
+            if !shallow_eq(&lhs, &rhs, &mut pending) {
+                return false;
+            }
+        }
+
+        true
+    }
 }
 
-impl Function {
-    /*
-        Takes a list of args from where it's called and the current env,
-        and attempts to bind each param symbol to it's positionally equivalent
-        arg within the current context.
-    */
-    pub fn bind_params(&mut self, args: Vec<Box<Form>>, env: &mut Environment) {
-        match *self.body {
-            Form::List(ref list) => {
-                for f in list.clone().elements {
-                    // println!("body element: {:?}", f);
+// Compares two forms without looking inside of them, pushing any elements they hold that still need comparing.
+fn shallow_eq(lhs: &Form, rhs: &Form, pending: &mut Vec<(Form, Form)>) -> bool {
+    match (lhs, rhs) {
+        (Form::Integer(a), Form::Integer(b)) => a == b,
+        (Form::BigInt(a), Form::BigInt(b)) => a == b,
+        (Form::Ratio(a), Form::Ratio(b)) => a == b,
+        (Form::Real(a), Form::Real(b)) => a == b || (a.is_nan() && b.is_nan()),
+        (Form::Str(a), Form::Str(b)) => a == b,
+        (Form::Char(a), Form::Char(b)) => a == b,
+        (Form::Bool(a), Form::Bool(b)) => a == b,
+        (Form::Symbol(a, _), Form::Symbol(b, _)) => a == b,
+        (Form::Keyword(a), Form::Keyword(b)) => a == b,
+        (Form::List(a), Form::List(b)) => push_elements(pending, a.elements(), b.elements()),
+        // a list built with cons is equal to one that was read or built with 'list',
+        // so they're compared a pair at a time: the first elements, and then the rest
+        (Form::Pair(_), Form::Pair(_) | Form::List(_)) | (Form::List(_), Form::Pair(_)) => {
+            match (lhs.uncons(), rhs.uncons()) {
+                (Some((a, a_rest)), Some((b, b_rest))) => {
+                    pending.push((a_rest, b_rest));
+                    pending.push((a, b));
+                    true
                 }
+                _ => false,
             }
-            _ => {
-                println!("aaaaa");
+        }
+        (Form::Map(a), Form::Map(b)) => {
+            Rc::ptr_eq(a, b)
+                || match_keys(a.keys(), b.keys()).is_some_and(|matched| {
+                    for (i, j) in matched {
+                        let (a_key, a_value) = a.get_index(i).unwrap();
+                        let (b_key, b_value) = b.get_index(j).unwrap();
+                        pending.push((a_value.clone(), b_value.clone()));
+                        pending.push((a_key.clone(), b_key.clone()));
+                    }
+                    true
+                })
+        }
+        (Form::Set(a), Form::Set(b)) => {
+            Rc::ptr_eq(a, b)
+                || match_keys(a.iter(), b.iter()).is_some_and(|matched| {
+                    for (i, j) in matched {
+                        pending.push((a[i].clone(), b[j].clone()));
+                    }
+                    true
+                })
+        }
+        (Form::Vector(a), Form::Vector(b)) => {
+            Rc::ptr_eq(a, b) || push_elements(pending, &a.borrow(), &b.borrow())
+        }
+        (Form::Function(a), Form::Function(b)) => a == b,
+        (Form::Macro(a), Form::Macro(b)) => a == b,
+        (Form::Nil(), Form::Nil()) => true,
+        _ => false,
+    }
+}
+
+// Pushes each pair of elements to be compared, if there are as many of one as the other.
+fn push_elements(pending: &mut Vec<(Form, Form)>, lhs: &[Form], rhs: &[Form]) -> bool {
+    if lhs.len() != rhs.len() {
+        return false;
+    }
+
+    pending.extend(zip(lhs, rhs).map(|(a, b)| (a.clone(), b.clone())));
+    true
+}
+
+/*
+    Pairs up the elements of two sets (or the keys of two maps) by their position,
+    so they can be compared like the elements of a list. Looking each one up in the
+    other set would compare nested sets by recursing, so elements are matched by
+    their hash instead: when only one element on each side has that hash they're
+    paired without comparing them, which is left to the caller.
+*/
+fn match_keys<'a>(
+    lhs: impl ExactSizeIterator<Item = &'a Form>,
+    rhs: impl ExactSizeIterator<Item = &'a Form>,
+) -> Option<Vec<(usize, usize)>> {
+    if lhs.len() != rhs.len() {
+        return None;
+    }
+
+    let hash_of = |form: &Form| {
+        let mut hasher = DefaultHasher::new();
+        form.hash(&mut hasher);
+        hasher.finish()
+    };
+
+    let mut candidates: HashMap<u64, Vec<(usize, &Form)>> = HashMap::new();
+    for (j, form) in rhs.enumerate() {
+        candidates.entry(hash_of(form)).or_default().push((j, form));
+    }
+
+    let mut matched = Vec::with_capacity(lhs.len());
+    for (i, form) in lhs.enumerate() {
+        let same_hash = candidates.get_mut(&hash_of(form))?;
+        let found = match same_hash.len() {
+            1 => 0,
+            _ => same_hash.iter().position(|(_, other)| *other == form)?,
+        };
+        matched.push((i, same_hash.swap_remove(found).0));
+    }
+
+    Some(matched)
+}
+
+/*
+    Forms are hashed structurally, the same way they're compared, so that any
+    form can be used as a map key or set element. Like comparing them, the
+    elements still to be hashed are kept on a stack rather than recursed into.
+*/
+impl Eq for Form {}
+
+impl Hash for Form {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut pending = vec![];
+        hash_shallow(self, state, &mut pending);
+
+        while let Some(form) = pending.pop() {
+            hash_shallow(&form, state, &mut pending);
+        }
+    }
+}
+
+// Hashes the form without looking inside of it, pushing any elements it holds that still need hashing.
+fn hash_shallow<H: Hasher>(form: &Form, state: &mut H, pending: &mut Vec<Form>) {
+    // A list and a chain of pairs with the same elements are equal, so both are hashed
+    // a pair at a time: the first element, and then the rest. Only the empty list
+    // can't be split, and it hashes as nothing at all.
+    if let Form::List(_) | Form::Pair(_) = form {
+        if let Some((car, cdr)) = form.uncons() {
+            pending.push(cdr);
+            pending.push(car);
+        }
+        return;
+    }
+
+    std::mem::discriminant(form).hash(state);
+    match form {
+        Form::Integer(i) => i.hash(state),
+        Form::BigInt(i) => i.hash(state),
+        Form::Ratio(r) => r.hash(state),
+        // 0.0 and -0.0 are equal, and so is every nan, so they have to hash the same
+        Form::Real(r) if r.is_nan() => f64::NAN.to_bits().hash(state),
+        Form::Real(r) => (if *r == 0.0 { 0.0 } else { *r }).to_bits().hash(state),
+        Form::Str(s) => s.hash(state),
+        Form::Char(c) => c.hash(state),
+        Form::Bool(b) => b.hash(state),
+        Form::Symbol(s, _) => s.hash(state),
+        Form::Keyword(k) => k.hash(state),
+        Form::Map(map) => hash_unordered(map.iter().map(|(k, v)| [k, v]), state),
+        Form::Set(set) => hash_unordered(set.iter().map(|entry| [entry]), state),
+        // a vector used as a key must not be changed while it's in the map
+        Form::Vector(vector) => {
+            let vector = vector.borrow();
+            vector.len().hash(state);
+            pending.extend(vector.iter().rev().cloned());
+        }
+        Form::Function(fun) | Form::Macro(fun) => {
+            fun.params.hash(state);
+            fun.body.hash(state);
+            Rc::as_ptr(&fun.env).hash(state);
+        }
+        Form::List(_) | Form::Pair(_) | Form::Nil() => {}
+    }
+}
+
+// Two maps (or sets) with the same entries are equal whatever order they were added in,
+// so the entries are hashed separately and combined in a way that ignores order.
+fn hash_unordered<'a, H, E>(entries: impl Iterator<Item = E>, state: &mut H)
+where
+    H: Hasher,
+    E: IntoIterator<Item = &'a Form>,
+{
+    let mut combined: u64 = 0;
+    let mut len: usize = 0;
+    for entry in entries {
+        let mut hasher = DefaultHasher::new();
+        for form in entry {
+            hash_surface(form, &mut hasher);
+        }
+        combined = combined.wrapping_add(hasher.finish());
+        len += 1;
+    }
+
+    len.hash(state);
+    combined.hash(state);
+}
+
+// Hashes a form without looking inside of it, which is still consistent with equality:
+// equal collections have the same kind and size, and lists and pairs hash as nothing.
+fn hash_surface<H: Hasher>(form: &Form, state: &mut H) {
+    match form {
+        Form::List(_) | Form::Pair(_) => {}
+        Form::Map(map) => {
+            std::mem::discriminant(form).hash(state);
+            map.len().hash(state);
+        }
+        Form::Set(set) => {
+            std::mem::discriminant(form).hash(state);
+            set.len().hash(state);
+        }
+        Form::Vector(vector) => {
+            std::mem::discriminant(form).hash(state);
+            vector.borrow().len().hash(state);
+        }
+        atom => hash_shallow(atom, state, &mut vec![]),
+    }
+}
+
+/*
+    A list of forms. Lists that were read from source text remember where they came
+    from, which is only used to report errors and never affects equality.
+*/
+#[derive(Clone)]
+pub struct List {
+    // The elements are shared between a list and every list taken from it with 'rest',
+    // which only has to move 'start' along rather than copy them.
+    items: Rc<[Form]>,
+    start: usize,
+    pub span: Option<Span>,
+}
+
+impl List {
+    pub fn new(elements: Vec<Form>) -> List {
+        List::with_span(elements, None)
+    }
+
+    pub fn with_span(elements: Vec<Form>, span: Option<Span>) -> List {
+        List {
+            items: Rc::from(elements),
+            start: 0,
+            span,
+        }
+    }
+
+    pub fn elements(&self) -> &[Form] {
+        &self.items[self.start..]
+    }
+
+    // Returns the list without its first element, sharing the rest of its elements.
+    pub fn rest(&self) -> List {
+        List {
+            items: Rc::clone(&self.items),
+            start: (self.start + 1).min(self.items.len()),
+            span: None,
+        }
+    }
+}
+
+/*
+    Dropping a form drops the forms inside of it, and then the forms inside of those,
+    recursing once per level until a deeply nested form overflows the stack. Lists
+    (and pairs) avoid this by moving whatever they hold that isn't shared elsewhere
+    out onto a stack of forms, which are then dropped one at a time.
+*/
+impl Drop for List {
+    fn drop(&mut self) {
+        if let Some(items) = Rc::get_mut(&mut self.items) {
+            if items.iter().any(Form::holds_forms) {
+                drop_nested(items.iter_mut().map(std::mem::take).collect());
             }
         }
+    }
+}
 
-        for (param, arg) in zip(&self.params, args) {
-            match (param, *arg) {
-                (Form::Symbol(param), Form::Symbol(arg)) => {
-                    // println!("Arg?: {arg:?}");
-                    let a = evaluate(Form::Symbol(arg), env);
-                    // println!("eval test in fn bind_params: {a:?}");
-                    self.context.bind_symbol(param.clone(), a);
+// Drops the forms, and everything inside of them, without recursing.
+pub fn drop_nested(mut pending: Vec<Form>) {
+    while let Some(mut form) = pending.pop() {
+        // once its contents are moved out, dropping the form has nothing left to recurse into
+        match &mut form {
+            Form::List(list) => {
+                if let Some(items) = Rc::get_mut(&mut list.items) {
+                    pending.extend(items.iter_mut().map(std::mem::take));
+                }
+            }
+            Form::Pair(pair) => {
+                if let Some(pair) = Rc::get_mut(pair) {
+                    pending.push(std::mem::take(&mut pair.car));
+                    pending.push(std::mem::take(&mut pair.cdr));
+                }
+            }
+            Form::Vector(vector) => {
+                if let Some(vector) = Rc::get_mut(vector) {
+                    vector.get_mut().take_forms(&mut pending);
                 }
-                (Form::Symbol(param), arg) => {
-                    println!("Arg?: {arg:?}");
-                    // let a = evaluate(Form::Symbol(arg), env);
-                    let eval = evaluate(arg, env);
-                    // println!("eval test in fn bind_params: {a:?}");
-                    self.context.bind_symbol(param.clone(), eval);
+            }
+            Form::Map(map) => {
+                if let Some(map) = Rc::get_mut(map) {
+                    map.take_forms(&mut pending);
+                }
+            }
+            Form::Set(set) => {
+                if let Some(set) = Rc::get_mut(set) {
+                    set.take_forms(&mut pending);
                 }
-                _ => {}
             }
-            // match param {
-            //     Form::Symbol(param) => {
-            //         // let a = evaluate(Form::Symbol(*param.clone().to_string()), env);
-            //         // println!("eval test in fn bind_params: {a:?}");
-            //         self.context.bind_symbol(param.clone(), *arg);
-            //     }
-            //     _ => {
-            //         println!("Error: formal parameter {param:?} is not a symbol");
-            //     }
-            // }
+            _ => {}
         }
     }
+}
+
+/*
+    The elements of a vector, map or set. Like lists and pairs, these are dropped
+    without recursing into each of their elements.
+*/
+#[derive(Clone, Default, Debug)]
+pub struct Contents<T: TakeForms>(T);
+
+// A collection of forms that can be emptied out, so that its forms can be dropped one at a time.
+pub trait TakeForms: Default {
+    fn with_capacity(capacity: usize) -> Self;
+    fn holds_forms(&self) -> bool;
+    fn take_forms(&mut self, into: &mut Vec<Form>);
+}
+
+impl TakeForms for Vec<Form> {
+    fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity(capacity)
+    }
+
+    fn holds_forms(&self) -> bool {
+        self.iter().any(Form::holds_forms)
+    }
+
+    fn take_forms(&mut self, into: &mut Vec<Form>) {
+        into.append(self);
+    }
+}
+
+impl TakeForms for IndexMap<Form, Form> {
+    fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity(capacity)
+    }
+
+    fn holds_forms(&self) -> bool {
+        self.iter()
+            .any(|(key, value)| key.holds_forms() || value.holds_forms())
+    }
+
+    fn take_forms(&mut self, into: &mut Vec<Form>) {
+        into.extend(self.drain(..).flat_map(|(key, value)| [key, value]));
+    }
+}
+
+impl TakeForms for IndexSet<Form> {
+    fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity(capacity)
+    }
+
+    fn holds_forms(&self) -> bool {
+        self.iter().any(Form::holds_forms)
+    }
+
+    fn take_forms(&mut self, into: &mut Vec<Form>) {
+        into.extend(self.drain(..));
+    }
+}
+
+impl<T: TakeForms> Drop for Contents<T> {
+    fn drop(&mut self) {
+        if self.0.holds_forms() {
+            let mut pending = vec![];
+            self.0.take_forms(&mut pending);
+            drop_nested(pending);
+        }
+    }
+}
+
+impl<T: TakeForms> Contents<T> {
+    pub fn new() -> Self {
+        Contents(T::default())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Contents(T::with_capacity(capacity))
+    }
+}
+
+impl<T: TakeForms> From<T> for Contents<T> {
+    fn from(contents: T) -> Self {
+        Contents(contents)
+    }
+}
+
+impl<T: TakeForms> Deref for Contents<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: TakeForms> DerefMut for Contents<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<A, T: TakeForms + FromIterator<A>> FromIterator<A> for Contents<T> {
+    fn from_iter<I: IntoIterator<Item = A>>(iter: I) -> Self {
+        Contents(T::from_iter(iter))
+    }
+}
+
+impl<'a, T: TakeForms> IntoIterator for &'a Contents<T>
+where
+    &'a T: IntoIterator,
+{
+    type Item = <&'a T as IntoIterator>::Item;
+    type IntoIter = <&'a T as IntoIterator>::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
 
-    pub fn unbind_params(&mut self) {
-        self.context.clear();
+impl PartialEq for List {
+    fn eq(&self, other: &Self) -> bool {
+        self.elements() == other.elements()
     }
+}
 
+impl fmt::Debug for List {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("List")
+            .field("elements", &self.elements())
+            .finish()
+    }
+}
+
+/*
+    A function closes over the frame it was created in, so its body can see
+    every binding in that scope, including ones made after the function was created.
+*/
+#[derive(Clone)]
+pub struct Function {
+    pub params: Vec<Form>,
+    pub env: Rc<Frame>,
+    pub body: Box<Form>,
+}
+
+// Two functions are only equal if they also close over the very same frame.
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        self.params == other.params && self.body == other.body && Rc::ptr_eq(&self.env, &other.env)
+    }
+}
+
+// The closed-over frame is left out, as it usually holds the function itself.
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Function")
+            .field("params", &self.params)
+            .field("body", &self.body)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Function {
     /*
-        Binds a symbol to a function's internal context by fetching it's Rc-Refcell
-        via an env lookup.
+        Takes a list of args from where it's called and the current env, and evaluates
+        each of them in the caller's scope before binding them to the function's params.
     */
-    pub fn bind_to_context(&mut self, env: &mut Environment, sym: String) {
-        let formref = env.bindings.last().unwrap().lookup_symbol(sym.clone());
-        if let Some(formref) = formref {
-            self.context.insert(sym, formref.clone());
-        } else {
-            println!("Error: could not find symbol {sym:?} in the environment.");
+    pub fn bind_params(
+        &self,
+        args: &[Form],
+        env: &mut Environment,
+    ) -> Result<Rc<Frame>, HanaError> {
+        let mut values = Vec::with_capacity(args.len());
+        for arg in args {
+            values.push(evaluate(arg.clone(), env)?);
         }
+
+        self.bind_values(values)
     }
 
     /*
-        Takes the current environment at time of creation and attempts to bind each explicitly
-        mentioned symbol to the fn's internal context, effectively closing over the env.
+        Allocates a brand new frame for a call, nested inside the frame the function
+        closed over, and binds each param symbol to it's positionally equivalent value
+        within that frame. A param following '&rest' is bound to a list of any values
+        left over, or nil if there are none.
+
+        Every call gets its own frame, so recursive and re-entrant calls never
+        see each other's arguments.
     */
-    pub fn close_over_env(&mut self, env: &mut Environment) {
-        self.env = env.clone();
+    pub fn bind_values(&self, values: Vec<Form>) -> Result<Rc<Frame>, HanaError> {
+        let (params, rest) = match self.params.iter().position(is_rest_param) {
+            Some(i) => (&self.params[..i], self.params.get(i + 1)),
+            None => (&self.params[..], None),
+        };
+
+        if values.len() < params.len() || (rest.is_none() && values.len() > params.len()) {
+            let expected = match rest {
+                Some(_) => format!(">= {}", params.len()),
+                None => params.len().to_string(),
+            };
+            return Err(HanaError::arity("lambda", &expected, values.len()));
+        }
+
+        let frame = Frame::new(Some(Rc::clone(&self.env)));
+        let mut values = values.into_iter();
+
+        for (param, value) in zip(params, values.by_ref()) {
+            if let Form::Symbol(param, _) = param {
+                frame.bind_symbol(param.clone(), value);
+            }
+        }
+
+        if let Some(Form::Symbol(rest, _)) = rest {
+            frame.bind_symbol(rest.clone(), list_or_nil(values.collect()));
+        }
+
+        Ok(frame)
+    }
+
+    /*
+        Calls the function with already evaluated args, and evaluates its body
+        straight away rather than handing it back to the evaluator as a tail call.
+    */
+    pub fn apply(&self, values: Vec<Form>, env: &mut Environment) -> HanaResult {
+        let frame = self.bind_values(values)?;
+
+        let caller = env.enter_frame(frame);
+        let result = evaluate((*self.body).clone(), env);
+        env.switch_frame(caller);
+
+        result
     }
 }
 
+// Marks the param that collects every remaining arg into a list, e.g. (a b &rest more)
+pub const REST_PARAM: &str = "&rest";
+
+pub fn is_rest_param(param: &Form) -> bool {
+    matches!(param, Form::Symbol(sym, _) if sym == REST_PARAM)
+}
+
 // Parses raw text and returns the code as a valid list of forms to be evaluated,
 // or an error if a grammar error is present.
-pub fn parse(source: &str) -> Result<Vec<Form>, Error<Rule>> {
-    let mut ast = vec![];
+pub fn parse(source: &str) -> Result<Vec<Form>, HanaError> {
+    parse_file("<input>", source)
+}
+
+// Same as 'parse', but errors raised by the returned forms are reported against
+// the given file name.
+pub fn parse_file(name: &str, source: &str) -> Result<Vec<Form>, HanaError> {
+    let source = Rc::new(Source {
+        name: name.to_string(),
+        text: source.to_string(),
+    });
+
+    check_nesting(&source)?;
+    let pairs = HanaParser::parse(Rule::program, &source.text)
+        .map_err(|e| HanaError::ParseError(e.with_path(name).to_string()))?;
 
-    let pairs = HanaParser::parse(Rule::program, source)?;
+    let mut ast = vec![];
 
     for pair in pairs {
         match pair.as_rule() {
             Rule::form => {
-                ast.push(build_ast_from_form(pair));
+                ast.push(build_ast_from_form(pair, &source)?);
             }
             Rule::quoted_form => {
-                // println!("Quoted form identified");
-                // println!("from parse, pair?: {pair:?}");
-                ast.push(build_ast_from_quoted_form(pair));
+                ast.push(build_ast_from_quoted_form(pair, &source)?);
             }
             _ => {}
         }
@@ -163,48 +682,88 @@ pub fn parse(source: &str) -> Result<Vec<Form>, Error<Rule>> {
 
 /*
     Helper function for parse, builds a valid AST-representation Form
-    from a quoted form grammar rule. Effectively replaces any instance of
-    ->    'form
+    from any of the quoting grammar rules. Effectively replaces any instance of
+    ->    'form   `form   ,form   ,@form
     with
-    ->    (quote form)
+    ->    (quote form)   (quasiquote form)   (unquote form)   (unquote-splicing form)
 */
-pub fn build_ast_from_quoted_form(pair: pest::iterators::Pair<Rule>) -> Form {
-    let mut quoted = List { elements: vec![] };
-    quoted
-        .elements
-        .push(Box::new(Form::Symbol("quote".to_string())));
-    // println!("PAIR: {pair:?}");
-    let f = build_ast_from_form(pair.into_inner().next().unwrap());
-    // println!("quoted form ast: {f:?}");
-    quoted.elements.push(Box::new(f));
-
-    Form::List(quoted)
+pub fn build_ast_from_quoted_form(pair: Pair<Rule>, source: &Rc<Source>) -> HanaResult {
+    let quote = match pair.as_rule() {
+        Rule::quasiquoted_form => "quasiquote",
+        Rule::unquoted_form => "unquote",
+        Rule::unquote_spliced_form => "unquote-splicing",
+        _ => "quote",
+    };
+    let span = Span::new(source, pair.as_span());
+    let f = build_ast_from_form(pair.into_inner().next().unwrap(), source)?;
+
+    Ok(Form::List(List::with_span(
+        vec![Form::Symbol(quote.to_string(), None), f],
+        Some(span),
+    )))
 }
 
 /*
     The 'Reader' for Hana. Takes a pest pair generated by the parser, and
-    constructs a valid form before returning it.
+    constructs a valid form before returning it. Literals that match the grammar
+    but can't be represented, like a real too large for a Real, are reported
+    as parse errors.
 */
-pub fn build_ast_from_form(pair: pest::iterators::Pair<Rule>) -> Form {
-    match pair.as_rule() {
+pub fn build_ast_from_form(pair: Pair<Rule>, source: &Rc<Source>) -> HanaResult {
+    let form = match pair.as_rule() {
+        // integers too big for an Integer are read as bigints instead
         Rule::integer => {
-            // println!("integer pair? {:?}", pair);
-            let i: i32 = pair.as_str().parse().unwrap();
-            Form::Integer(i)
+            let i: BigInt = pair
+                .as_str()
+                .parse()
+                .map_err(|_| invalid_literal(&pair, source))?;
+            Number::from_bigint(i).into_form()
+        }
+        Rule::radix_integer => {
+            let radix = match pair.as_str().as_bytes()[1] {
+                b'x' | b'X' => 16,
+                b'o' | b'O' => 8,
+                _ => 2,
+            };
+            let i = BigInt::parse_bytes(&pair.as_str().as_bytes()[2..], radix)
+                .ok_or_else(|| invalid_literal(&pair, source))?;
+            Number::from_bigint(i).into_form()
+        }
+        Rule::invalid_number => return Err(invalid_literal(&pair, source)),
+        Rule::ratio => {
+            let r: Ratio = pair
+                .as_str()
+                .parse()
+                .map_err(|_| invalid_literal(&pair, source))?;
+            Number::from_ratio(r).into_form()
         }
         Rule::real => {
-            let r: f64 = pair.as_str().parse().unwrap();
+            let r: Real = pair
+                .as_str()
+                .parse()
+                .map_err(|_| invalid_literal(&pair, source))?;
+            // only 'inf' itself is allowed to be infinite
+            if r.is_infinite() && !pair.as_str().ends_with("inf") {
+                return Err(read_error(
+                    &pair,
+                    source,
+                    &format!("real literal '{}' is out of range", pair.as_str()),
+                ));
+            }
             Form::Real(r)
         }
         Rule::string => {
             let str = &pair.as_str();
-            // println!("{}", str);
-            let str = &str[1..str.len() - 1];
-            // println!("{}", str);
-            // should be: \" -> "
-            let str = str.replace("\\\"", "\"");
+            let str = unescape(&str[1..str.len() - 1])
+                .map_err(|reason| read_error(&pair, source, &reason))?;
+
+            Form::Str(Str::from(str))
+        }
 
-            Form::Str(CString::new(&str[..]).unwrap())
+        Rule::char => {
+            let c = char_from_name(&pair.as_str()[2..])
+                .ok_or_else(|| read_error(&pair, source, "unknown character name"))?;
+            Form::Char(c)
         }
 
         Rule::bool => {
@@ -214,29 +773,349 @@ pub fn build_ast_from_form(pair: pest::iterators::Pair<Rule>) -> Form {
 
         Rule::nil => Form::Nil(),
 
-        Rule::symbol => Form::Symbol(String::from(pair.as_str())),
+        Rule::symbol => Form::Symbol(
+            String::from(pair.as_str()),
+            Some(Span::new(source, pair.as_span())),
+        ),
+
+        Rule::keyword => Form::Keyword(Keyword::intern(&pair.as_str()[1..])),
 
         Rule::list => {
-            let mut pair = pair.into_inner();
+            let span = Span::new(source, pair.as_span());
+            let dotted = pair.clone().into_inner().any(|p| p.as_rule() == Rule::dot);
+            let mut elements = pair
+                .clone()
+                .into_inner()
+                .filter(|p| p.as_rule() == Rule::form)
+                .map(|p| build_ast_from_form(p, source))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            if !dotted {
+                return Ok(Form::List(List::with_span(elements, Some(span))));
+            }
+
+            // (a b . c) is built from the back, consing each element onto the form after the dot
+            let cdr = elements.pop().unwrap_or_default();
+            if elements.is_empty() {
+                return Err(read_error(
+                    &pair,
+                    source,
+                    "a dotted list needs at least one form before the '.'",
+                ));
+            }
+            elements
+                .into_iter()
+                .rev()
+                .fold(cdr, |cdr, car| pair::cons(car, cdr))
+        }
+
+        Rule::vector => {
+            let elements = pair
+                .into_inner()
+                .filter(|p| p.as_rule() != Rule::datum_comment)
+                .map(|p| build_ast_from_form(p, source))
+                .collect::<Result<_, _>>()?;
+
+            vector::new_vector(elements)
+        }
+
+        Rule::map => {
+            let forms = pair
+                .clone()
+                .into_inner()
+                .filter(|p| p.as_rule() != Rule::datum_comment)
+                .map(|p| build_ast_from_form(p, source))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            if forms.len() % 2 != 0 {
+                return Err(read_error(
+                    &pair,
+                    source,
+                    "a map needs a value for every key",
+                ));
+            }
+
+            let mut map = Map::with_capacity(forms.len() / 2);
+            let mut forms = forms.into_iter();
+            while let (Some(key), Some(value)) = (forms.next(), forms.next()) {
+                if map.contains_key(&key) {
+                    return Err(read_error(
+                        &pair,
+                        source,
+                        &format!("duplicate key {key} in map"),
+                    ));
+                }
+                map.insert(key, value);
+            }
+
+            Form::Map(Rc::new(map))
+        }
+
+        Rule::set => {
+            let elements = pair
+                .clone()
+                .into_inner()
+                .filter(|p| p.as_rule() != Rule::datum_comment)
+                .map(|p| build_ast_from_form(p, source))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let mut set = Set::with_capacity(elements.len());
+            for elem in elements {
+                if set.contains(&elem) {
+                    return Err(read_error(
+                        &pair,
+                        source,
+                        &format!("duplicate element {elem} in set"),
+                    ));
+                }
+                set.insert(elem);
+            }
+
+            Form::Set(Rc::new(set))
+        }
+
+        Rule::quoted_form
+        | Rule::quasiquoted_form
+        | Rule::unquoted_form
+        | Rule::unquote_spliced_form => build_ast_from_quoted_form(pair, source)?,
 
-            let mut elements = Vec::new();
+        Rule::form => build_ast_from_form(pair.into_inner().next().unwrap(), source)?,
+        _ => Form::Nil(),
+    };
+
+    Ok(form)
+}
+
+// Builds a parse error pointing at the pair, rendered the same way as pest's own errors.
+fn read_error(pair: &Pair<Rule>, source: &Source, message: &str) -> HanaError {
+    let err = PestError::<Rule>::new_from_span(
+        ErrorVariant::CustomError {
+            message: message.to_string(),
+        },
+        pair.as_span(),
+    );
+
+    HanaError::ParseError(err.with_path(&source.name).to_string())
+}
+
+// How deeply forms can be nested in source text, since both the parser and the reader
+// recurse once for every level.
+pub const MAX_READ_DEPTH: usize = 256;
+
+/*
+    Checks that no form in the source is nested deeper than MAX_READ_DEPTH, before the
+    parser gets the chance to overflow the stack. Each list, vector, map and set counts
+    as a level, and so does each quote (or unquote, or datum comment) in front of a form.
+*/
+fn check_nesting(source: &Source) -> Result<(), HanaError> {
+    let text = source.text.as_bytes();
+    // the depth to go back to once each open list is closed
+    let mut open = vec![];
+    let mut depth = 0;
+    // quotes that are still waiting for the form they apply to
+    let mut prefixes = 0;
+    let mut i = 0;
+
+    while i < text.len() {
+        let rest = &text[i..];
+        let start = i;
+
+        if rest.starts_with(b"#|") {
+            i = skip_block_comment(text, start);
+        } else if rest.starts_with(b",@") || rest.starts_with(b"#;") {
+            i += 2;
+            prefixes += 1;
+            depth += 1;
+        } else if rest.starts_with(b"#{") {
+            i += 2;
+            open.push(depth - prefixes);
+            depth += 1;
+            prefixes = 0;
+        } else {
+            i += 1;
+            match rest[0] {
+                b'\'' | b'`' | b',' => {
+                    prefixes += 1;
+                    depth += 1;
+                }
+                b'(' | b'[' | b'{' => {
+                    open.push(depth - prefixes);
+                    depth += 1;
+                    prefixes = 0;
+                }
+                b')' | b']' | b'}' => {
+                    depth = open.pop().unwrap_or(0);
+                    prefixes = 0;
+                }
+                b';' => i += rest.iter().position(|&c| c == b'\n').unwrap_or(rest.len()),
+                c if c.is_ascii_whitespace() => {}
+                // anything else is an atom, which completes the form its quotes were waiting for
+                c => {
+                    i = skip_atom(text, start, c);
+                    depth -= prefixes;
+                    prefixes = 0;
+                }
+            }
+        }
+
+        if depth > MAX_READ_DEPTH {
+            let position = pest::Position::new(&source.text, start)
+                .expect("nesting only grows at a bracket or a quote");
+            let err = PestError::<Rule>::new_from_pos(
+                ErrorVariant::CustomError {
+                    message: format!("forms can't be nested more than {MAX_READ_DEPTH} deep"),
+                },
+                position,
+            );
+            return Err(HanaError::ParseError(
+                err.with_path(&source.name).to_string(),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// Returns the index just past the (possibly nested) block comment starting at 'start'.
+fn skip_block_comment(text: &[u8], start: usize) -> usize {
+    let mut comments = 0;
+    let mut i = start;
+    while i < text.len() {
+        if text[i..].starts_with(b"#|") {
+            comments += 1;
+            i += 2;
+        } else if text[i..].starts_with(b"|#") {
+            comments -= 1;
+            i += 2;
+            if comments == 0 {
+                break;
+            }
+        } else {
+            i += 1;
+        }
+    }
+    i
+}
 
-            while let Some(p) = pair.next() {
-                // println!("{:?}", p);
-                elements.push(Box::new(build_ast_from_form(p)));
+// Returns the index just past the string, character or other token starting at 'start'.
+fn skip_atom(text: &[u8], start: usize, first: u8) -> usize {
+    let mut i = start + 1;
+    match first {
+        b'"' => {
+            while i < text.len() && text[i] != b'"' {
+                i += if text[i] == b'\\' { 2 } else { 1 };
             }
+            return (i + 1).min(text.len());
+        }
+        // the character after #\ is part of it, even if it would end a token, e.g. #\(
+        b'#' if text.get(i) == Some(&b'\\') => i += 2,
+        _ => {}
+    }
+
+    while i < text.len() && !is_token_end(text[i]) {
+        i += 1;
+    }
+    i.min(text.len())
+}
+
+fn is_token_end(c: u8) -> bool {
+    c.is_ascii_whitespace() || b"()[]{}\"'`,;".contains(&c)
+}
+
+fn invalid_literal(pair: &Pair<Rule>, source: &Source) -> HanaError {
+    read_error(
+        pair,
+        source,
+        &format!("'{}' is not a valid number", pair.as_str()),
+    )
+}
+
+// Names for the characters that can't be written as themselves after #\
+pub const CHAR_NAMES: [(&str, char); 6] = [
+    ("space", ' '),
+    ("newline", '\n'),
+    ("tab", '\t'),
+    ("return", '\r'),
+    ("nul", '\0'),
+    ("backspace", '\u{8}'),
+];
+
+/*
+    Decodes what follows the #\ of a character literal, which is either a single
+    character, one of the CHAR_NAMES, or an x followed by a unicode scalar value in hex.
+*/
+fn char_from_name(name: &str) -> Option<char> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(c);
+    }
+
+    if let Some((_, c)) = CHAR_NAMES.iter().find(|(n, _)| *n == name) {
+        return Some(*c);
+    }
+
+    let hex = name.strip_prefix(['x', 'X'])?;
+    u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+}
+
+/*
+    Decodes the escape sequences in the body of a string literal:
+
+        \n  newline          \"  double quote
+        \t  tab              \'  single quote
+        \r  carriage return  \\  backslash
+        \0  nul              \u{1F338}  any unicode scalar value, in hex
 
-            Form::List(List { elements })
+    Returns a description of the problem if an escape isn't one of these.
+*/
+fn unescape(str: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(str.len());
+    let mut chars = str.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some('0') => out.push('\0'),
+            Some(c @ ('"' | '\'' | '\\')) => out.push(c),
+            Some('u') => out.push(unescape_unicode(&mut chars)?),
+            Some(c) => return Err(format!("unknown escape sequence '\\{c}'")),
+            None => return Err("a string can't end with a lone '\\'".to_string()),
         }
+    }
+
+    Ok(out)
+}
 
-        Rule::quoted_form => build_ast_from_quoted_form(pair.into_inner().next().unwrap()),
+// Decodes the '{1F338}' part of a \u{1F338} escape.
+fn unescape_unicode(chars: &mut std::str::Chars) -> Result<char, String> {
+    let mut hex = String::new();
+    let mut closed = false;
 
-        Rule::form => build_ast_from_form(pair.into_inner().next().unwrap()),
-        _ => {
-            // println!("Hitting edge case in build_ast_from_form");
-            Form::Nil()
+    if chars.next() == Some('{') {
+        for c in chars.by_ref() {
+            if c == '}' {
+                closed = true;
+                break;
+            }
+            hex.push(c);
         }
     }
+
+    if !closed || hex.is_empty() || hex.len() > 6 {
+        return Err("unicode escapes are written as \\u{XXXX}, with 1 to 6 hex digits".to_string());
+    }
+
+    u32::from_str_radix(&hex, 16)
+        .ok()
+        .and_then(char::from_u32)
+        .ok_or_else(|| format!("'\\u{{{hex}}}' is not a unicode scalar value"))
 }
 
 /*
@@ -250,98 +1129,240 @@ pub type Context = HashMap<Symbol, Rc<RefCell<Form>>>;
 */
 pub trait ContextExt {
     fn bind_symbol(&mut self, symbol: Symbol, value: Form);
-    fn bind_symbol_from_refcell(&mut self, symbol: Symbol, value: &Rc<RefCell<Form>>);
-    fn lookup_symbol(&self, symbol: Symbol) -> Option<&Rc<RefCell<Form>>>;
+    fn lookup_symbol(&self, symbol: &str) -> Option<&Rc<RefCell<Form>>>;
 }
 
 impl ContextExt for Context {
     fn bind_symbol(&mut self, symbol: Symbol, value: Form) {
-        // println!("symbol: {symbol:?}, value: {value:?}");
-        match value {
-            Form::Symbol(value) => {
-                if let Some(lookup) = self.lookup_symbol(value.clone()) {
-                    // println!(
-                    //     "Found binding from symbol {:?} to value {:?}",
-                    //     value, lookup
-                    // );
-                    let symref = Rc::clone(lookup);
-                    self.insert(symbol, symref);
-                } else {
-                    println!("Error: could not find a binding with symbol {:?}", symbol);
-                }
-            }
-            _ => {
-                self.insert(symbol, Rc::new(RefCell::new(value)));
+        self.insert(symbol, Rc::new(RefCell::new(value)));
+    }
+
+    fn lookup_symbol(&self, symbol: &str) -> Option<&Rc<RefCell<Form>>> {
+        self.get(symbol)
+    }
+}
+
+/*
+    A frame holds the bindings made in a single lexical scope, along with a pointer to the
+    frame of the scope enclosing it. Frames are shared through Rc's, so closures and calls
+    can hold on to a scope without copying any of its bindings.
+*/
+pub struct Frame {
+    pub bindings: RefCell<Context>,
+    pub parent: Option<Rc<Frame>>,
+}
+
+impl Frame {
+    pub fn new(parent: Option<Rc<Frame>>) -> Rc<Frame> {
+        Rc::new(Frame {
+            bindings: RefCell::new(Context::new()),
+            parent,
+        })
+    }
+
+    // Binds a form to a symbol in this frame, shadowing any binding in an enclosing frame.
+    pub fn bind_symbol(&self, symbol: Symbol, value: Form) {
+        self.bindings.borrow_mut().bind_symbol(symbol, value);
+    }
+
+    // Attempts to find a form bound to the given symbol, starting from this frame
+    // and working outwards through each enclosing frame.
+    // returns None if a binding is not found, otherwise returns the binding.
+    pub fn lookup_symbol(&self, symbol: &str) -> Option<Rc<RefCell<Form>>> {
+        let mut frame = self;
+        loop {
+            if let Some(found) = frame.bindings.borrow().lookup_symbol(symbol) {
+                return Some(Rc::clone(found));
             }
+            frame = frame.parent.as_deref()?;
         }
     }
-    fn bind_symbol_from_refcell(&mut self, symbol: Symbol, value: &Rc<RefCell<Form>>) {
-        // println!("symbol: {symbol:?}, value: {value:?}");
-        let symref = Rc::clone(value);
-        self.insert(symbol, symref);
+}
+
+/*
+    A closure that's bound in the frame it closes over, e.g. by a let or a def inside of a
+    function, makes a cycle of Rc's that would keep the frame alive forever. Once a scope
+    is left, if the only other references to its frame come from closures bound in it,
+    nothing else can reach them any more, so the bindings are cleared to break the cycle.
+    The same goes for each enclosing frame that was only being kept alive by this one.
+*/
+fn release_frame(mut frame: Rc<Frame>) {
+    loop {
+        // the global frame is the environment's for as long as it's around
+        if frame.parent.is_some() && Rc::strong_count(&frame) == 1 + closures_over(&frame) {
+            let bindings = std::mem::take(&mut *frame.bindings.borrow_mut());
+            drop(bindings);
+        }
+
+        match Rc::try_unwrap(frame) {
+            Ok(Frame {
+                parent: Some(parent),
+                ..
+            }) => frame = parent,
+            _ => break,
+        }
     }
+}
+
+// Counts the functions bound directly in the frame that close over it, and can only be reached from it.
+fn closures_over(frame: &Rc<Frame>) -> usize {
+    frame
+        .bindings
+        .borrow()
+        .values()
+        .filter(|cell| Rc::strong_count(cell) == 1)
+        .filter(|cell| match &*cell.borrow() {
+            Form::Function(fun) | Form::Macro(fun) => Rc::ptr_eq(&fun.env, frame),
+            _ => false,
+        })
+        .count()
+}
 
-    fn lookup_symbol(&self, symbol: Symbol) -> Option<&Rc<RefCell<Form>>> {
-        self.get(&symbol)
+// Only the names bound in the frame are shown, as the values can refer back to the frame.
+impl fmt::Debug for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Frame")
+            .field(
+                "bindings",
+                &self.bindings.borrow().keys().collect::<Vec<_>>(),
+            )
+            .field("parent", &self.parent)
+            .finish()
     }
 }
 
 /*
     An environment is a global struct that holds contextual information required to evaluate
-    forms in Hana (and Lisp more generally). In Hana specifically, the environment holds
-    a lexical stack of 'Context's, each of which are a HashMap that maps symbols to
-    a valid form held in a Rc<RefCell<Form>>.
+    forms in Hana (and Lisp more generally). In Hana specifically, the environment points at
+    the 'Frame' of the scope currently being evaluated, which is chained through its parents
+    back to the global frame.
+
+    The environment also owns the sink that console output builtins like 'print' write to.
+    It's stdout by default, but can be swapped out to capture a program's output.
+
+    Finally, it keeps track of how deeply evaluation is nested, along with the names of the
+    functions currently being called. Evaluation nested deeper than 'max_depth' raises a
+    stack overflow error instead of overflowing the Rust stack and taking the host down with it.
 */
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Clone)]
 pub struct Environment {
-    pub bindings: Vec<Context>,
+    pub frame: Rc<Frame>,
+    pub output: Rc<RefCell<dyn Write>>,
+    pub depth: usize,
+    pub max_depth: usize,
+    pub call_stack: Vec<Symbol>,
+}
+
+impl fmt::Debug for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Environment")
+            .field("frame", &self.frame)
+            .field("depth", &self.depth)
+            .field("max_depth", &self.max_depth)
+            .field("call_stack", &self.call_stack)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Environment::new()
+    }
 }
 
 impl Environment {
+    // Creates an environment holding only an empty global frame.
     pub fn new() -> Environment {
-        Environment { bindings: vec![] }
+        Environment {
+            frame: Frame::new(None),
+            output: Rc::new(RefCell::new(io::stdout())),
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            call_stack: vec![],
+        }
     }
-    // Attempts to bind a valid form to a symbol in the topmost context in the context-stack.
-    pub fn bind_symbol(&mut self, symbol: Symbol, value: Form) {
-        let len = self.bindings.len();
-        let ctx = self.bindings.get_mut(len - 1).unwrap();
 
-        ctx.bind_symbol(symbol, value);
+    // Sets how deeply evaluation may nest before raising a stack overflow error.
+    // The host is responsible for running the interpreter on a stack that's big enough.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
     }
+
+    // Redirects console output from builtins like 'print' to the given sink.
+    pub fn set_output(&mut self, output: Rc<RefCell<dyn Write>>) {
+        self.output = output;
+    }
+
+    // Writes the string to the output sink, flushing it so that partial
+    // lines show up straight away.
+    pub fn write_output(&self, s: &str) -> Result<(), HanaError> {
+        let mut output = self.output.borrow_mut();
+        output
+            .write_all(s.as_bytes())
+            .and_then(|_| output.flush())
+            .map_err(|e| HanaError::Io(e.to_string()))
+    }
+
+    // Attempts to bind a valid form to a symbol in the current frame.
+    pub fn bind_symbol(&mut self, symbol: Symbol, value: Form) {
+        self.frame.bind_symbol(symbol, value);
+    }
+
     // Attempts to find a form bound to the given symbol (passed as string), starting
-    // from the top of the context stack, working its way down.
+    // from the current frame, working its way out to the global frame.
     // returns None if a binding is not found, otherwise returns the binding.
     pub fn lookup_symbol(&self, symbol: Symbol) -> Option<Rc<RefCell<Form>>> {
-        for ctx in self.bindings.iter().rev() {
-            if let Some(found) = ctx.lookup_symbol(symbol.clone()) {
-                return Some(Rc::clone(found));
-            }
+        self.frame.lookup_symbol(&symbol)
+    }
+
+    // Returns the outermost frame, which holds every global binding.
+    pub fn global_frame(&self) -> Rc<Frame> {
+        let mut frame = Rc::clone(&self.frame);
+        while let Some(parent) = frame.parent.clone() {
+            frame = parent;
+        }
+        frame
+    }
+
+    // Makes the given frame the current scope, returning the frame it replaced
+    // so that it can be restored once the new scope is done with.
+    pub fn enter_frame(&mut self, frame: Rc<Frame>) -> Rc<Frame> {
+        std::mem::replace(&mut self.frame, frame)
+    }
+
+    // Makes the given frame the current scope, leaving the one it replaced for good.
+    pub fn switch_frame(&mut self, frame: Rc<Frame>) {
+        let left = self.enter_frame(frame);
+        if !Rc::ptr_eq(&left, &self.frame) {
+            release_frame(left);
         }
-        None
     }
 
-    // Pushes a new context to the top of the context-stack.
-    pub fn push_new_context(&mut self) {
-        self.bindings.push(Context::new());
+    // Opens a new, empty scope nested inside the current one.
+    pub fn push_frame(&mut self) {
+        self.frame = Frame::new(Some(Rc::clone(&self.frame)));
     }
 
-    pub fn push_context(&mut self, ctx: Context) {
-        // println!("Push context");
-        self.bindings.push(ctx);
+    // Closes the current scope, returning to the one enclosing it.
+    pub fn pop_frame(&mut self) {
+        if let Some(parent) = self.frame.parent.clone() {
+            self.switch_frame(parent);
+        }
     }
 
-    // Pops the topmost context from the context-stack.
-    #[allow(dead_code)]
-    pub fn pop_context(&mut self) {
-        // println!("Pop context");
-        self.bindings.pop();
+    // The error raised when evaluation goes deeper than 'max_depth'.
+    pub fn stack_overflow(&self) -> HanaError {
+        HanaError::StackOverflow {
+            max_depth: self.max_depth,
+            call_stack: self.call_stack.clone(),
+        }
     }
 }
 
 /*
     The 'Evaluator' for Hana. Takes a valid form, and the environment in which
     the form is to be evaluated in, and returns the result of the evaluation
-    as a valid form.
+    as a valid form, or the error that stopped evaluation.
 
     Some forms in Hana (and Lisps generally) are considered to be 'self-evaluating'
     when the result of evaluating a form is the form itself, I.E. evaluate(form) = form.
@@ -351,195 +1372,291 @@ impl Environment {
     This means that the resulting evaluation of any valid Hana program will produce
     a self-evaluating form as a result.
 */
-pub fn evaluate(form: Form, env: &mut Environment) -> Form {
-    match form {
-        Form::Integer(_) => form,
+pub fn evaluate(mut form: Form, env: &mut Environment) -> HanaResult {
+    if env.depth >= env.max_depth {
+        return Err(env.stack_overflow());
+    }
+    env.depth += 1;
+
+    // Forms in tail position are evaluated by going around this loop again rather than
+    // recursing, so they don't grow the Rust stack. Tail calls leave their own frame as
+    // the current scope, so the caller's scope is put back once the loop is done.
+    // Likewise, a tail call replaces the call it was made from on the call stack.
+    let scope = Rc::clone(&env.frame);
+    let calls = env.call_stack.len();
+
+    // A macro that expands into a call to itself would go around the loop forever
+    // without growing the stack, so every expansion since the last call counts
+    // against the depth limit as well. Expansions rarely come from the source, so
+    // an error points at the macro call that started them.
+    let mut expansions = 0;
+    let mut expanded_from = None;
+
+    let result = loop {
+        match form {
+            Form::Symbol(sym, span) => {
+                break match env.lookup_symbol(sym.clone()) {
+                    Some(found) => Ok(found.borrow().clone()),
+                    None => Err(HanaError::UnboundSymbol(sym).at(&span)),
+                };
+            }
+
+            Form::List(list) => match evaluate_list(&list, env) {
+                Ok(Tail::Return(result)) => break Ok(result),
+                Ok(Tail::Eval(next)) => form = next,
+                Ok(Tail::Call(name, body)) => {
+                    env.call_stack.truncate(calls);
+                    env.call_stack.push(name);
+                    expansions = 0;
+                    expanded_from = None;
+                    form = body;
+                }
+                Ok(Tail::Expand(name, expansion)) => {
+                    expansions += 1;
+                    expanded_from = expanded_from.or_else(|| list.span.clone());
+                    if env.depth + expansions > env.max_depth {
+                        env.call_stack.push(name);
+                        break Err(env.stack_overflow().at(&expanded_from));
+                    }
+                    form = expansion;
+                }
+                Err(e) => break Err(e.at(&list.span)),
+            },
+
+            // a list built out of pairs, e.g. by a macro, is evaluated like any other list
+            Form::Pair(_) => match form.list_elements() {
+                Some(elements) => form = Form::List(List::new(elements.into_owned())),
+                None => {
+                    break Err(HanaError::invalid_form(
+                        &form.to_string(),
+                        "an improper list can't be evaluated",
+                    ))
+                }
+            },
 
-        Form::Real(_) => form,
+            Form::Map(map) => break evaluate_map(&map, env),
+            Form::Set(set) => break evaluate_set(&set, env),
+            Form::Vector(vector) => break evaluate_vector(&vector, env),
 
-        Form::Str(_) => form,
+            // Everything else is self-evaluating
+            _ => break Ok(form),
+        }
+    };
 
-        Form::Bool(_) => form,
+    env.switch_frame(scope);
+    env.call_stack.truncate(calls);
+    env.depth -= 1;
 
-        Form::Symbol(form) => {
-            let mut result = env.lookup_symbol(form.clone());
+    result
+}
 
-            if result.is_some() {
-                // let test = result.unwrap().borrow_mut().to_owned();
-                let x = result.as_mut().unwrap();
-                let tmp = x.as_ref();
-                // println!("reading from rc refcell? {:?}", tmp);
-                // println!("reading from rc refcell? {:?}", i32::from(tmp.into_inner()));
-                // println!(
-                //     "reading from rc refcell? {:?}",
-                //     *result.unwrap().borrow_mut()
-                // );
+/*
+    What's left to do after evaluating a list: either it produced a result,
+    or there's a form in tail position that still needs to be evaluated
+    in the (possibly new) current scope of the environment. 'Call' is the
+    body of the named function, which has just been called, and 'Expand'
+    is the expansion of a call to the named macro.
+*/
+pub enum Tail {
+    Return(Form),
+    Eval(Form),
+    Call(Symbol, Form),
+    Expand(Symbol, Form),
+}
 
-                let test = tmp.borrow_mut().clone();
-                // println!("cloned refcell: {:?}", test);
-                return test;
-            }
-            println!("Error: symbol {form:?} is not bound in the current environment.");
-            Form::Nil()
-        }
-
-        Form::List(list) => {
-            /*
-            List Evaluation Order:
-            1. Special-Form
-                A special form is a list whose first element is a symbol that matches
-                a pre-defined set of names, set aside by the language for special use.
-                Each S-Form has a non-standard behaviour and evaluation protocol, hence
-                the name "special".
-
-            2. Macro Call [NOT-IMPLEMENTED]
-                Macros are not yet supported.
-
-            3. Function Call
-                A function call is when the evaluating list's first element is a symbol
-                that matches a defined function. Every following element of the list
-                is considered to be arguments to the function.
-            */
-
-            /*
-                Function Call Implementation:
-                ======================================
-                Check if list[0] is a symbol,
-                if no:
-                    fail
-                else:
-                    lookup = evaluate(list[0])
-
-                    Check if lookup is a lambda,
-                    if yes:
-                        1. create a new context,
-                        2. bind args to formal params in that new context,
-                        3. Parse lambda-body list for non-param symbols
-                            and bind any found to the new context
-                        4. move new context into the environment,
-                        5. evaluate the lambda
-
-                    if no:
-                        fail
-            */
-            // let elements = &list.elements;
-
-            // if it's an empty list, return nil
-            if list.elements.len() == 0 {
-                return Form::Nil();
+/*
+    Evaluates a list form, which is either a special form or a function call.
+*/
+fn evaluate_list(list: &List, env: &mut Environment) -> Result<Tail, HanaError> {
+    /*
+    List Evaluation Order:
+    1. Special-Form
+        A special form is a list whose first element is a symbol that matches
+        a pre-defined set of names, set aside by the language for special use.
+        Each S-Form has a non-standard behaviour and evaluation protocol, hence
+        the name "special".
+
+    2. Builtin Call
+        A builtin call is when the evaluating list's first element is a symbol that
+        names one of the built-in functions, and the program hasn't bound that name
+        itself. A definition, parameter or macro with the same name shadows the builtin.
+
+    3. Macro Call
+        A macro call is when the evaluating list's first element is a symbol
+        bound to a macro. The macro's transformer is called with the rest of the
+        list as its unevaluated arguments, and whatever form it returns (the expansion)
+        is evaluated in place of the macro call.
+
+    4. Function Call
+        A function call is when the evaluating list's first element is a symbol
+        that matches a defined function. Every following element of the list
+        is considered to be arguments to the function.
+
+    5. Keyword Lookup
+        A list whose first element is a keyword looks the keyword up in the map
+        that follows it, i.e. (:name person) is (get person :name).
+    */
+
+    // if it's an empty list, return nil
+    let Some((first, args)) = list.elements().split_first() else {
+        return Ok(Tail::Return(Form::Nil()));
+    };
+
+    if let Form::Symbol(sym, _) = first {
+        if let Some(tail) = tail_special_form(sym, list, env) {
+            return tail;
+        }
+        if env.frame.lookup_symbol(sym).is_none() {
+            if let Some(builtin) = builtin_function(sym, list, env) {
+                return builtin.map(Tail::Return);
             }
+        }
+    }
 
-            let first = *list.elements[0].clone();
+    // Anything else in the first position (a symbol bound to a function,
+    // or a list that resolves to one) is evaluated before attempting a fn call.
+    let name = match first {
+        Form::Symbol(sym, _) => sym.clone(),
+        _ => "lambda".to_string(),
+    };
+
+    match evaluate(first.clone(), env)? {
+        Form::Macro(mac) => Ok(Tail::Expand(name, mac.apply(args.to_vec(), env)?)),
+        Form::Function(fun) => call_function(name, fun, args, env),
+        Form::Keyword(keyword) => call_keyword(keyword, args, env).map(Tail::Return),
+        other => Err(HanaError::NotCallable(Box::new(other))),
+    }
+}
 
-            // fetch the first element, check if it's a symbol,
-            // if it is then bind the args to the function's parameters
-            match first {
-                Form::Symbol(first) => {
-                    if let Some(builtin) = builtin_function(&first, &list, env) {
-                        // println!("Built-in function found: {first:?}");
-                        return builtin;
-                    }
-                    if let Some(lookup) = env.lookup_symbol(first) {
-                        // println!("Lookup: {:?}", lookup);
-
-                        let fun = lookup.as_ref().borrow_mut().clone();
-
-                        // if the symbol is a function, treat it as a function call
-                        match fun {
-                            Form::Function(mut fun) => {
-                                // println!("[DEBUG] Valid function form");
-
-                                // println!("\tparams: {:?}", fun.params);
-                                // println!("\tcontext: {:?}", fun.context);
-                                // println!("\tenv: {:?}", fun.env);
-                                // println!("\tbody: {:?}", fun.body);
-                                // println!("\n");
-
-                                // println!("\t=Matching param to args=");
-                                // println!("\t=Testing bind_params=");
-
-                                // create a new context, and for each formal parameter, bind the args passed
-                                // in the form being evaluated
-                                // env.push_context();
-
-                                if let Some((_, rest)) = list.elements.split_first() {
-                                    fun.bind_params(rest.to_vec(), env);
-                                }
-
-                                /*
-                                iterate through the function's env back to front, and clone each context
-                                into the current env.
-                                */
-                                // println!("global env (before): {env:?}");
-
-                                env.push_context(fun.context.clone());
-                                for it in fun.env.bindings.iter().rev() {
-                                    env.push_context(it.clone());
-                                }
-                                // println!("global env (after): {env:?}");
-
-                                // println!("[DEBUG] Valid function form");
-
-                                // println!("\tparams: {:?}", fun.params);
-                                // println!("\tcontext: {:?}", fun.context);
-                                // println!("\tenv: {:?}", fun.env);
-                                // println!("\tbody: {:?}", fun.body);
-                                // println!("\n");
-
-                                let ret = evaluate(*fun.body, env);
-
-                                for i in (0..fun.env.bindings.len() + 1) {
-                                    env.pop_context();
-                                }
-                                // fun.unbind_params();
-
-                                // println!("fun ctx after call?: {:?}", fun.context);
-
-                                return ret;
-                            }
-                            _ => {
-                                println!("Error: evaluated list is not a valid form");
-                            }
-                        }
-                    }
-                }
-                // If handed a list, assume it's a function that resolves to a function
-                // and evaluate it before attempting a fn call, otherwise error out
-                Form::List(_) => {
-                    let fun = evaluate(first.clone(), env);
-                    match fun {
-                        Form::Function(mut fun) => {
-                            if let Some((_, rest)) = list.elements.split_first() {
-                                fun.bind_params(rest.to_vec(), env);
-                            }
-                            // println!("Evaluating function!");
-                            // println!("current fun.context?: {:?}", fun.context);
-
-                            env.push_context(fun.context.clone());
-
-                            // println!("current context?: {:?}", env.bindings.last().unwrap());
-                            let ret = evaluate(*fun.body, env);
-                            env.pop_context();
-                            return ret;
-                        }
-                        _ => {
-                            println!("Error: expected first element in list form to resolve to a function.");
-                        }
-                    }
-                }
-                _ => {
-                    println!("Error: evaluated list is not a valid form");
-                }
+/*
+    Function Call Implementation:
+    ======================================
+    1. bind args to formal params in a fresh frame for this call,
+    2. make that frame the current scope,
+    3. hand the lambda body back to 'evaluate' as a form in tail position.
+        The caller's scope is restored by 'evaluate' once the body is done.
+*/
+fn call_function(
+    name: Symbol,
+    fun: Function,
+    args: &[Form],
+    env: &mut Environment,
+) -> Result<Tail, HanaError> {
+    let frame = fun.bind_params(args, env)?;
+
+    env.switch_frame(frame);
+
+    Ok(Tail::Call(name, *fun.body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_can_be_captured() {
+        let captured = Rc::new(RefCell::new(Vec::<u8>::new()));
+        let mut env = Environment::new();
+        env.set_output(captured.clone());
+
+        for form in parse(r#"(print "a" 1) (write "a") (newline)"#).unwrap() {
+            evaluate(form, &mut env).unwrap();
+        }
+
+        assert_eq!(captured.borrow().as_slice(), b"a 1\"a\"\n");
+    }
+
+    // Evaluates the source, returning the first error without the location it was raised at.
+    fn eval_error(source: &str) -> HanaError {
+        let mut env = Environment::new();
+        for form in parse(source).unwrap() {
+            match evaluate(form, &mut env) {
+                Ok(_) => {}
+                Err(HanaError::At(_, err)) => return *err,
+                Err(err) => return err,
             }
+        }
+        panic!("{source} should fail");
+    }
+
+    #[test]
+    fn errors_are_returned_to_the_caller() {
+        assert_eq!(eval_error("x"), HanaError::UnboundSymbol("x".to_string()));
+        assert_eq!(eval_error("(/ 1 0)"), HanaError::DivisionByZero);
+        assert_eq!(
+            eval_error("(1 2)"),
+            HanaError::NotCallable(Box::new(Form::Integer(1)))
+        );
+        assert_eq!(
+            eval_error("(car 1)"),
+            HanaError::type_mismatch("car", "a list", Form::Integer(1))
+        );
+        assert!(matches!(
+            eval_error("(car)"),
+            HanaError::Arity { function, found: 0, .. } if function == "car"
+        ));
+        assert!(matches!(
+            eval_error("(lambda (1) 1)"),
+            HanaError::InvalidForm { .. }
+        ));
+    }
+
+    #[test]
+    fn nil_results_are_not_errors() {
+        let mut env = Environment::new();
+        for source in ["nil", "(if false 1)", "(def x 1)", "(cdr (list 1))"] {
+            let form = parse(source).unwrap().remove(0);
+            assert_eq!(evaluate(form, &mut env), Ok(Form::Nil()), "{source}");
+        }
+    }
 
-            for elem in list.elements {
-                let res = evaluate(*elem, env);
-                // println!("evaluated: {res:?}");
+    #[test]
+    fn closures_bound_in_their_own_frame_are_freed() {
+        // every frame that's kept alive holds on to the global frame through its parents
+        for def in [
+            "(def f (lambda (n) (let ((g (lambda (x) x))) (g n))))",
+            "(def f (lambda (n) (progn (def g (lambda (x) x)) (g n))))",
+            "(def f (lambda (n) (let ((g (lambda (x) x))) (+ 1 (g n)))))",
+            "(defmacro f (n) (let ((g (lambda (x) x))) (g n)))",
+            "(def f (lambda (n) (each i x (list n) (let ((g (lambda (y) y))) (g x)))))",
+        ] {
+            let mut env = Environment::new();
+            evaluate(parse(def).unwrap().remove(0), &mut env).unwrap();
+            let call = parse("(f 1)").unwrap().remove(0);
+
+            let before = Rc::strong_count(&env.frame);
+            for _ in 0..100 {
+                evaluate(call.clone(), &mut env).unwrap();
             }
-            // println!("elements: {:?}", elements);
-            Form::Nil()
+            assert_eq!(Rc::strong_count(&env.frame), before, "{def}");
         }
+    }
 
-        _ => Form::Nil(),
+    #[test]
+    fn escaping_closures_keep_their_frame() {
+        let mut env = Environment::new();
+        let source = "
+            (def make (lambda (n) (let ((get (lambda () n))) get)))
+            (def get-5 (make 5))
+            (get-5)";
+
+        let mut result = Form::Nil();
+        for form in parse(source).unwrap() {
+            result = evaluate(form, &mut env).unwrap();
+        }
+        assert_eq!(result, Form::Integer(5));
+    }
+
+    #[test]
+    fn errors_unwind_through_mutual_recursion() {
+        let source = "
+            (def even? (lambda (n) (if (= n 0) (car n) (+ 1 (odd? (- n 1))))))
+            (def odd? (lambda (n) (+ 1 (even? (- n 1)))))
+            (even? 10)";
+
+        assert_eq!(
+            eval_error(source),
+            HanaError::type_mismatch("car", "a list", Form::Integer(0))
+        );
     }
 }
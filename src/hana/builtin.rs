@@ -1,30 +1,42 @@
-use core::str;
-use std::iter::Map;
-
+use std::cmp::Ordering;
+
+use crate::hana::keyword::*;
+use crate::hana::macros::*;
+use crate::hana::map::*;
+use crate::hana::number::Number;
+use crate::hana::pair::*;
+use crate::hana::set::*;
 use crate::hana::special::*;
+use crate::hana::string::*;
+use crate::hana::vector::*;
 use crate::hana::*;
 
-pub const BUILTIN_SYMBOLS: [&str; 12] = [
-    "lambda", "lambda", "if", "+", "-", "*", "/", "<", "<=", ">", ">=", "=",
-];
-
 // Takes refs to a symbol and the current environment, and compares the symbol
 // against a set of built-in functions
-pub fn builtin_function(symbol: &Symbol, funcall: &List, env: &mut Environment) -> Option<Form> {
-    match symbol.as_str() {
+pub fn builtin_function(
+    symbol: &Symbol,
+    funcall: &List,
+    env: &mut Environment,
+) -> Option<HanaResult> {
+    let result = match symbol.as_str() {
         "quote" => handle_quote(funcall, env),
+        "quasiquote" => handle_quasiquote(funcall, env),
+        "unquote" | "unquote-splicing" => Err(HanaError::invalid_form(
+            symbol,
+            "can only be used inside of a quasiquote",
+        )),
         "lambda" => make_lambda(funcall, env),
         "def" => def_symbol(funcall, env),
         "car" => handle_car(funcall, env),
         "cdr" => handle_cdr(funcall, env),
-        "progn" => handle_progn(funcall, env),
-        "let" => handle_let(funcall, env),
+        "cons" => handle_cons(funcall, env),
+        "pair?" => handle_is_pair(funcall, env),
+        "list?" => handle_is_list(funcall, env),
         "set" => set_symbol(funcall, env),
         "each" => handle_each(funcall, env),
         "list" => create_list(funcall, env),
         "nth" => handle_nth(funcall, env),
         "len" => handle_len(funcall, env),
-        "if" => handle_if(funcall, env),
         "+" => handle_add(funcall, env),
         "-" => handle_sub(funcall, env),
         "*" => handle_mul(funcall, env),
@@ -35,672 +47,489 @@ pub fn builtin_function(symbol: &Symbol, funcall: &List, env: &mut Environment)
         ">=" => handle_gte(funcall, env),
         "=" => handle_eq(funcall, env),
         "!=" => handle_neq(funcall, env),
+        "defmacro" => def_macro(funcall, env),
+        "macroexpand-1" => handle_macroexpand_1(funcall, env),
+        "macroexpand" => handle_macroexpand(funcall, env),
+        "exit" => handle_exit(funcall, env),
+        "print" => handle_print(funcall, env),
+        "println" => handle_println(funcall, env),
+        "write" => handle_write(funcall, env),
+        "newline" => handle_newline(funcall, env),
+        "str" => handle_str(funcall, env),
+        "substring" => handle_substring(funcall, env),
+        "string-length" => handle_string_length(funcall, env),
+        "string-split" => handle_string_split(funcall, env),
+        "string-join" => handle_string_join(funcall, env),
+        "string-trim" => handle_string_trim(funcall, env),
+        "upcase" => handle_upcase(funcall, env),
+        "downcase" => handle_downcase(funcall, env),
+        "starts-with?" => handle_starts_with(funcall, env),
+        "ends-with?" => handle_ends_with(funcall, env),
+        "string-index" => handle_string_index(funcall, env),
+        "string-replace" => handle_string_replace(funcall, env),
+        "string->list" => handle_string_to_list(funcall, env),
+        "list->string" => handle_list_to_string(funcall, env),
+        "format" => handle_format(funcall, env),
+        "vector" => handle_vector(funcall, env),
+        "vec" => handle_vec(funcall, env),
+        "vector-ref" => handle_vector_ref(funcall, env),
+        "vector-set!" => handle_vector_set(funcall, env),
+        "vector-push!" => handle_vector_push(funcall, env),
+        "subvec" => handle_subvec(funcall, env),
+        "get" => handle_get(funcall, env),
+        "assoc" => handle_assoc(funcall, env),
+        "dissoc" => handle_dissoc(funcall, env),
+        "contains?" => handle_contains(funcall, env),
+        "keys" => handle_keys(funcall, env),
+        "vals" => handle_vals(funcall, env),
+        "merge" => handle_merge(funcall, env),
+        "update" => handle_update(funcall, env),
+        "hash-set" => handle_hash_set(funcall, env),
+        "list->set" => handle_list_to_set(funcall, env),
+        "conj" => handle_conj(funcall, env),
+        "disj" => handle_disj(funcall, env),
+        "union" => handle_union(funcall, env),
+        "intersection" => handle_intersection(funcall, env),
+        "difference" => handle_difference(funcall, env),
+        "subset?" => handle_is_subset(funcall, env),
+        "keyword?" => handle_is_keyword(funcall, env),
+        "keyword" => handle_keyword(funcall, env),
+        "name" => handle_name(funcall, env),
+        "char?" => handle_is_char(funcall, env),
+        "char-alphabetic?" => handle_char_alphabetic(funcall, env),
+        "char-whitespace?" => handle_char_whitespace(funcall, env),
+        "char->integer" => handle_char_to_integer(funcall, env),
+        "integer->char" => handle_integer_to_char(funcall, env),
         _ => {
             return None;
         }
-    }
-}
-
-fn handle_add(funcall: &List, env: &mut Environment) -> Option<Form> {
-    if funcall.elements.len() < 3 {
-        println!("Error: function '+' takes >= 2 parameters");
-        return Some(Form::Nil());
-    }
-
-    let mut itr = funcall.elements.iter();
-
-    // skip the function name
-    itr.next();
-
-    let mut sum: Real = 0.0;
-
-    while let Some(itr) = itr.next() {
-        // println!("individual elem: {itr:?}");
+    };
 
-        let evaluated = evaluate(*itr.clone(), env);
+    Some(result)
+}
 
-        // println!("Evaluated?: {evaluated:?}");
-        match evaluated {
-            Form::Integer(evaluated) => {
-                sum += evaluated as Real;
-            }
+/*
+    Checks that a function call was handed at least 'min' (and at most 'max', if given)
+    arguments, not counting the function name itself. Returns the arguments on success.
+*/
+pub fn expect_args<'a>(
+    funcall: &'a List,
+    name: &str,
+    min: usize,
+    max: Option<usize>,
+) -> Result<&'a [Form], HanaError> {
+    let args = funcall.elements().get(1..).unwrap_or(&[]);
+
+    let in_range = args.len() >= min && max.is_none_or(|max| args.len() <= max);
+    if in_range {
+        return Ok(args);
+    }
+
+    let expected = match max {
+        Some(max) if max == min => min.to_string(),
+        Some(max) => format!("{min} to {max}"),
+        None => format!(">= {min}"),
+    };
+    Err(HanaError::arity(name, &expected, args.len()))
+}
 
-            Form::Real(evaluated) => {
-                sum += evaluated;
-            }
-            _ => {
-                println!("Error: {evaluated:?} is not a a number.");
-                return Some(Form::Nil());
-            }
-        }
+/*
+    Evaluates an index into a string, list or vector of the given length. When 'end'
+    is set, the index may also point just past the last element, like the end of a range.
+*/
+pub fn eval_index(
+    name: &str,
+    form: &Form,
+    length: usize,
+    end: bool,
+    env: &mut Environment,
+) -> Result<usize, HanaError> {
+    let index = match evaluate(form.clone(), env)? {
+        Form::Integer(i) => BigInt::from(i),
+        Form::BigInt(i) => i,
+        other => return Err(HanaError::type_mismatch(name, "an integer index", other)),
+    };
 
-        // println!("individual evaluated elem: {evaluated:?}");
+    let limit = if end { length + 1 } else { length };
+    match usize::try_from(&index) {
+        Ok(i) if i < limit => Ok(i),
+        _ => Err(HanaError::IndexOutOfRange {
+            function: name.to_string(),
+            index,
+            length,
+        }),
     }
-
-    return Some(Form::Real(sum));
 }
 
-// fn handle_add(funcall: &List, env: &mut Environment) -> Option<Form> {}
-
-fn handle_sub(funcall: &List, env: &mut Environment) -> Option<Form> {
-    if funcall.elements.len() < 3 {
-        println!("Error: function '+' takes >= 2 parameters");
-        return Some(Form::Nil());
+// Builtins that produce a list return nil rather than an empty one.
+pub fn list_or_nil(elements: Vec<Form>) -> Form {
+    match elements.is_empty() {
+        true => Form::Nil(),
+        false => Form::List(List::new(elements)),
     }
+}
 
-    let mut itr = funcall.elements.iter();
-
-    // skip the function name
-    itr.next();
+// Evaluates a form that is expected to produce a number, widening integers to reals.
+fn eval_number(name: &str, form: &Form, env: &mut Environment) -> Result<Number, HanaError> {
+    let evaluated = evaluate(form.clone(), env)?;
 
-    let mut sub: Real = 0.0;
+    Number::from_form(&evaluated)
+        .ok_or_else(|| HanaError::type_mismatch(name, "a number", evaluated))
+}
 
-    if let Some(itr) = itr.next() {
-        let evaluated = evaluate(*itr.clone(), env);
-        match evaluated {
-            Form::Real(evaluated) => sub = evaluated,
+/*
+    Folds the arguments of an arithmetic function call from left to right
+    using the given operator.
+*/
+fn handle_arithmetic(
+    funcall: &List,
+    env: &mut Environment,
+    name: &str,
+    op: fn(Number, Number) -> Result<Number, HanaError>,
+) -> HanaResult {
+    let args = expect_args(funcall, name, 2, None)?;
 
-            Form::Integer(evaluated) => sub = evaluated as Real,
-            _ => {
-                println!("Error: argument {evaluated:?} is not a number.");
-                return Some(Form::Nil());
-            }
-        }
+    let mut acc = eval_number(name, &args[0], env)?;
+    for arg in &args[1..] {
+        acc = op(acc, eval_number(name, arg, env)?)?;
     }
 
-    while let Some(itr) = itr.next() {
-        println!("individual elem: {itr:?}");
-
-        let evaluated = evaluate(*itr.clone(), env);
+    Ok(acc.into_form())
+}
 
-        match evaluated {
-            Form::Integer(evaluated) => {
-                sub -= evaluated as Real;
-            }
+fn handle_add(funcall: &List, env: &mut Environment) -> HanaResult {
+    handle_arithmetic(funcall, env, "+", |a, b| Ok(a.add(b)))
+}
 
-            Form::Real(evaluated) => {
-                sub -= evaluated;
-            }
-            _ => {
-                println!("Error: {evaluated:?} is not a a number.");
-                return Some(Form::Nil());
-            }
-        }
+fn handle_sub(funcall: &List, env: &mut Environment) -> HanaResult {
+    handle_arithmetic(funcall, env, "-", |a, b| Ok(a.sub(b)))
+}
 
-        println!("individual evaluated elem: {evaluated:?}");
-    }
+fn handle_mul(funcall: &List, env: &mut Environment) -> HanaResult {
+    handle_arithmetic(funcall, env, "*", |a, b| Ok(a.mul(b)))
+}
 
-    return Some(Form::Real(sub));
+fn handle_div(funcall: &List, env: &mut Environment) -> HanaResult {
+    handle_arithmetic(funcall, env, "/", Number::div)
 }
 
-fn handle_mul(funcall: &List, env: &mut Environment) -> Option<Form> {
-    if funcall.elements.len() < 3 {
-        println!("Error: function '*' takes >= 2 parameters");
-        return Some(Form::Nil());
-    }
+/*
+    Compares two numerical forms with the given inequality.
+*/
+fn handle_inequality(
+    funcall: &List,
+    env: &mut Environment,
+    name: &str,
+    op: fn(Ordering) -> bool,
+) -> HanaResult {
+    let args = expect_args(funcall, name, 2, Some(2))?;
+
+    let lhs = eval_number(name, &args[0], env)?;
+    let rhs = eval_number(name, &args[1], env)?;
+
+    // NaN is neither less nor greater than anything
+    Ok(Form::Bool(lhs.compare(&rhs).is_some_and(op)))
+}
 
-    let mut itr = funcall.elements.iter();
+fn handle_lt(funcall: &List, env: &mut Environment) -> HanaResult {
+    handle_inequality(funcall, env, "<", Ordering::is_lt)
+}
 
-    // skip the function name
-    itr.next();
+fn handle_lte(funcall: &List, env: &mut Environment) -> HanaResult {
+    handle_inequality(funcall, env, "<=", Ordering::is_le)
+}
 
-    let mut product: Real = 0.0;
+fn handle_gt(funcall: &List, env: &mut Environment) -> HanaResult {
+    handle_inequality(funcall, env, ">", Ordering::is_gt)
+}
 
-    if let Some(itr) = itr.next() {
-        let evaluated = evaluate(*itr.clone(), env);
-        match evaluated {
-            Form::Real(evaluated) => product = evaluated,
+fn handle_gte(funcall: &List, env: &mut Environment) -> HanaResult {
+    handle_inequality(funcall, env, ">=", Ordering::is_ge)
+}
 
-            Form::Integer(evaluated) => product = evaluated as Real,
-            _ => {
-                println!("Error: argument {evaluated:?} is not a number.");
-                return Some(Form::Nil());
-            }
-        }
+/*
+    Two numbers are equal when they have the same value, regardless of where
+    they are in the numeric tower, so (= 1 1.0) and (= 1/2 0.5) are true. Any other forms are equal
+    when they're structurally the same.
+*/
+fn forms_equal(lhs: &Form, rhs: &Form) -> bool {
+    match (Number::from_form(lhs), Number::from_form(rhs)) {
+        (Some(a), Some(b)) => a.compare(&b) == Some(Ordering::Equal),
+        _ => lhs == rhs,
     }
+}
 
-    while let Some(itr) = itr.next() {
-        println!("individual elem: {itr:?}");
+/*
+    Compares the value of two forms. If the form is a symbol,
+    the value that's bound to it will be compared.
+*/
+fn handle_eq(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "=", 2, Some(2))?;
 
-        let evaluated = evaluate(*itr.clone(), env);
+    let lhs = evaluate(args[0].clone(), env)?;
+    let rhs = evaluate(args[1].clone(), env)?;
 
-        match evaluated {
-            Form::Integer(evaluated) => {
-                product *= evaluated as Real;
-            }
+    Ok(Form::Bool(forms_equal(&lhs, &rhs)))
+}
 
-            Form::Real(evaluated) => {
-                product *= evaluated;
-            }
-            _ => {
-                println!("Error: {evaluated:?} is not a a number.");
-                return Some(Form::Nil());
-            }
-        }
+fn handle_neq(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "!=", 2, Some(2))?;
 
-        println!("individual evaluated elem: {evaluated:?}");
-    }
+    let lhs = evaluate(args[0].clone(), env)?;
+    let rhs = evaluate(args[1].clone(), env)?;
 
-    return Some(Form::Real(product));
+    Ok(Form::Bool(!forms_equal(&lhs, &rhs)))
 }
 
-fn handle_div(funcall: &List, env: &mut Environment) -> Option<Form> {
-    if funcall.elements.len() < 3 {
-        println!("Error: function '*' takes >= 2 parameters");
-        return Some(Form::Nil());
-    }
-
-    let mut itr = funcall.elements.iter();
+/*
+    Creates a new function via the (lambda) function call, closing over
+    the scope it was created in.
+*/
+fn make_lambda(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "lambda", 2, Some(2))?;
 
-    // skip the function name
-    itr.next();
+    let fun = build_function("lambda", &args[0], &args[1], env)?;
 
-    let mut quot: Real = 0.0;
+    Ok(Form::Function(fun))
+}
 
-    if let Some(itr) = itr.next() {
-        let evaluated = evaluate(*itr.clone(), env);
-        match evaluated {
-            Form::Real(evaluated) => quot = evaluated,
+/*
+    Checks that a parameter list is made up of only symbols, with at most one
+    '&rest' followed by exactly one symbol, and then builds a function out of it.
+    The function captures the current scope, effectively closing over every binding
+    that's visible from it.
+*/
+pub fn build_function(
+    name: &str,
+    params: &Form,
+    body: &Form,
+    env: &Environment,
+) -> Result<Function, HanaError> {
+    let Form::List(params) = params else {
+        return Err(HanaError::invalid_form(
+            name,
+            "the parameter list must be a list of symbols",
+        ));
+    };
 
-            Form::Integer(evaluated) => quot = evaluated as Real,
-            _ => {
-                println!("Error: argument {evaluated:?} is not a number.");
-                return Some(Form::Nil());
-            }
+    if let Some(param) = params
+        .elements()
+        .iter()
+        .find(|p| !matches!(p, Form::Symbol(_, _)))
+    {
+        return Err(HanaError::invalid_form(
+            name,
+            &format!("formal parameter {param} is not a symbol"),
+        ));
+    }
+
+    if let Some(i) = params.elements().iter().position(is_rest_param) {
+        if i + 2 != params.elements().len() {
+            return Err(HanaError::invalid_form(
+                name,
+                &format!("'{REST_PARAM}' must be followed by exactly one symbol"),
+            ));
         }
     }
 
-    while let Some(itr) = itr.next() {
-        println!("individual elem: {itr:?}");
-
-        let evaluated = evaluate(*itr.clone(), env);
-
-        match evaluated {
-            Form::Integer(evaluated) => {
-                if evaluated == 0 {
-                    println!("Error: cannot divide by zero");
-                    return Some(Form::Nil());
-                }
-                quot /= evaluated as Real;
-            }
+    Ok(Function {
+        params: params.elements().to_vec(),
+        env: Rc::clone(&env.frame),
+        body: Box::new(body.clone()),
+    })
+}
 
-            Form::Real(evaluated) => {
-                if evaluated == 0.0 {
-                    println!("Error: cannot divide by zero");
-                    return Some(Form::Nil());
-                }
-                quot /= evaluated;
-            }
-            _ => {
-                println!("Error: {evaluated:?} is not a a number.");
-                return Some(Form::Nil());
-            }
-        }
+fn handle_car(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "car", 1, Some(1))?;
 
-        println!("individual evaluated elem: {evaluated:?}");
+    match evaluate(args[0].clone(), env)? {
+        Form::Pair(pair) => Ok(pair.car.clone()),
+        Form::List(lst) => Ok(lst.elements().first().cloned().unwrap_or_default()),
+        Form::Nil() => Ok(Form::Nil()),
+        other => Err(HanaError::type_mismatch("car", "a list", other)),
     }
-
-    return Some(Form::Real(quot));
 }
 
-fn handle_lt(funcall: &List, env: &mut Environment) -> Option<Form> {
-    if funcall.elements.len() < 3 {
-        println!("Error: function '*' takes >= 2 parameters");
-        return Some(Form::Nil());
-    }
-    let mut itr = funcall.elements.iter();
-    itr.next();
-
-    let mut lhs: f64 = 0.0;
-    let mut rhs: f64 = 0.0;
-
-    if let Some(l) = itr.next() {
-        let eval = evaluate(*l.clone(), env);
-        match eval {
-            Form::Integer(eval) => lhs = eval as f64,
-            Form::Real(eval) => lhs = eval,
-            _ => {
-                println!("Error: Cannot compare inequality for non-numerical types.");
-                return Some(Form::Nil());
-            }
-        }
-    }
-    if let Some(r) = itr.next() {
-        let eval = evaluate(*r.clone(), env);
-        match eval {
-            Form::Integer(eval) => rhs = eval as f64,
-            Form::Real(eval) => rhs = eval,
-            _ => {
-                println!("Error: Cannot compare inequality for non-numerical types.");
-                return Some(Form::Nil());
-            }
-        }
-    }
+fn handle_cdr(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "cdr", 1, Some(1))?;
 
-    Some(Form::Bool(lhs < rhs))
-}
-fn handle_lte(funcall: &List, env: &mut Environment) -> Option<Form> {
-    if funcall.elements.len() < 3 {
-        println!("Error: function '*' takes >= 2 parameters");
-        return Some(Form::Nil());
-    }
-    let mut itr = funcall.elements.iter();
-    itr.next();
-
-    let mut lhs: f64 = 0.0;
-    let mut rhs: f64 = 0.0;
-
-    if let Some(l) = itr.next() {
-        let eval = evaluate(*l.clone(), env);
-        match eval {
-            Form::Integer(eval) => lhs = eval as f64,
-            Form::Real(eval) => lhs = eval,
-            _ => {
-                println!("Error: Cannot compare inequality for non-numerical types.");
-                return Some(Form::Nil());
-            }
-        }
-    }
-    if let Some(r) = itr.next() {
-        let eval = evaluate(*r.clone(), env);
-        match eval {
-            Form::Integer(eval) => rhs = eval as f64,
-            Form::Real(eval) => rhs = eval,
-            _ => {
-                println!("Error: Cannot compare inequality for non-numerical types.");
-                return Some(Form::Nil());
+    match evaluate(args[0].clone(), env)? {
+        Form::Pair(pair) => Ok(pair.cdr.clone()),
+        // the rest of the list shares its elements, so this doesn't copy them
+        Form::List(lst) => {
+            if lst.elements().len() <= 1 {
+                return Ok(Form::Nil());
             }
+
+            Ok(Form::List(lst.rest()))
         }
+        Form::Nil() => Ok(Form::Nil()),
+        other => Err(HanaError::type_mismatch("cdr", "a list", other)),
     }
-
-    Some(Form::Bool(lhs <= rhs))
 }
 
-fn handle_gt(funcall: &List, env: &mut Environment) -> Option<Form> {
-    if funcall.elements.len() < 3 {
-        println!("Error: function '*' takes >= 2 parameters");
-        return Some(Form::Nil());
-    }
-    let mut itr = funcall.elements.iter();
-    itr.next();
-
-    let mut lhs: f64 = 0.0;
-    let mut rhs: f64 = 0.0;
-
-    if let Some(l) = itr.next() {
-        let eval = evaluate(*l.clone(), env);
-        match eval {
-            Form::Integer(eval) => lhs = eval as f64,
-            Form::Real(eval) => lhs = eval,
-            _ => {
-                println!("Error: Cannot compare inequality for non-numerical types.");
-                return Some(Form::Nil());
-            }
-        }
-    }
-    if let Some(r) = itr.next() {
-        let eval = evaluate(*r.clone(), env);
-        match eval {
-            Form::Integer(eval) => rhs = eval as f64,
-            Form::Real(eval) => rhs = eval,
-            _ => {
-                println!("Error: Cannot compare inequality for non-numerical types.");
-                return Some(Form::Nil());
-            }
-        }
-    }
+fn create_list(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "list", 0, None)?;
 
-    Some(Form::Bool(lhs > rhs))
-}
-fn handle_gte(funcall: &List, env: &mut Environment) -> Option<Form> {
-    if funcall.elements.len() < 3 {
-        println!("Error: function '*' takes >= 2 parameters");
-        return Some(Form::Nil());
-    }
-    let mut itr = funcall.elements.iter();
-    itr.next();
-
-    let mut lhs: f64 = 0.0;
-    let mut rhs: f64 = 0.0;
-
-    if let Some(l) = itr.next() {
-        let eval = evaluate(*l.clone(), env);
-        match eval {
-            Form::Integer(eval) => lhs = eval as f64,
-            Form::Real(eval) => lhs = eval,
-            _ => {
-                println!("Error: Cannot compare inequality for non-numerical types.");
-                return Some(Form::Nil());
-            }
-        }
-    }
-    if let Some(r) = itr.next() {
-        let eval = evaluate(*r.clone(), env);
-        match eval {
-            Form::Integer(eval) => rhs = eval as f64,
-            Form::Real(eval) => rhs = eval,
-            _ => {
-                println!("Error: Cannot compare inequality for non-numerical types.");
-                return Some(Form::Nil());
-            }
-        }
+    let mut elements = Vec::with_capacity(args.len());
+
+    for arg in args {
+        elements.push(evaluate(arg.clone(), env)?);
     }
 
-    Some(Form::Bool(lhs >= rhs))
+    Ok(Form::List(List::new(elements)))
 }
 
-/*
-    Compares the value of two forms. If the form is a symbol,
-    the value that's bound to it will be compared.
-*/
-fn handle_eq(funcall: &List, env: &mut Environment) -> Option<Form> {
-    if funcall.elements.len() < 3 {
-        println!("Error: function '*' takes >= 2 parameters");
-        return Some(Form::Nil());
-    }
-    let mut itr = funcall.elements.iter();
-    itr.next();
-    let mut lhs = *itr
-        .next()
-        .expect("Error: cannot retrieve second argument from function call to eq")
-        .clone();
-    let mut rhs = *itr
-        .next()
-        .expect("Error: cannot retrieve third argument from function call to eq")
-        .clone();
-
-    lhs = evaluate(lhs, env);
-    rhs = evaluate(rhs, env);
-
-    Some(Form::Bool(lhs == rhs))
-
-    // Some(Form::Nil())
-}
+fn handle_nth(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "nth", 2, Some(2))?;
 
-fn handle_neq(funcall: &List, env: &mut Environment) -> Option<Form> {
-    if funcall.elements.len() < 3 {
-        println!("Error: function '*' takes >= 2 parameters");
-        return Some(Form::Nil());
-    }
-    let mut itr = funcall.elements.iter();
-    itr.next();
-    let mut lhs = *itr
-        .next()
-        .expect("Error: cannot retrieve second argument from function call to eq")
-        .clone();
-    let mut rhs = *itr
-        .next()
-        .expect("Error: cannot retrieve third argument from function call to eq")
-        .clone();
-
-    lhs = evaluate(lhs, env);
-    rhs = evaluate(rhs, env);
-
-    Some(Form::Bool(lhs != rhs))
-
-    // Some(Form::Nil())
-}
+    // a negative index, or one too big to fit in an integer, is never in range
+    let index = match evaluate(args[0].clone(), env)? {
+        Form::Integer(index) => usize::try_from(index).ok(),
+        Form::BigInt(_) => None,
+        other => return Err(HanaError::type_mismatch("nth", "an integer", other)),
+    };
 
-/*
-    Creates a new function via the (lambda) function call, closing over
-    any referenced symbols in the environment at time of creation.
-*/
-fn make_lambda(funcall: &List, env: &mut Environment) -> Option<Form> {
-    if funcall.elements.len() < 3 {
-        println!("Error: function 'lambda' takes >= 2 parameters");
-        return Some(Form::Nil());
-    }
+    let seq = evaluate(args[1].clone(), env)?;
 
-    let mut fun: Function = Function {
-        params: vec![],
-        context: HashMap::new(),
-        env: Environment::new(),
-        body: Box::new(Form::Nil()),
+    let Some(index) = index else {
+        return Ok(Form::Nil());
     };
 
-    // Skip the function name
-    let mut itr = funcall.elements.iter();
-    itr.next();
-
-    // copy the parameter form
-    if let Some(params) = itr.next() {
-        match *params.clone() {
-            Form::List(params) => {
-                // println!("params?: {params:?}");
-                for elem in params.elements {
-                    fun.params.push(*elem);
+    match seq {
+        Form::List(list) => Ok(list.elements().get(index).cloned().unwrap_or_default()),
+        Form::Vector(vector) => Ok(vector.borrow().get(index).cloned().unwrap_or_default()),
+        Form::Str(s) => Ok(s.chars().nth(index).map_or(Form::Nil(), Form::Char)),
+        Form::Nil() => Ok(Form::Nil()),
+        Form::Pair(pair) => {
+            let mut tail = Form::Pair(pair);
+            for _ in 0..index {
+                match tail.uncons() {
+                    Some((_, cdr)) => tail = cdr,
+                    None => return Ok(Form::Nil()),
                 }
             }
-            _ => {
-                println!("Error: ");
-            }
+            Ok(tail.uncons().map(|(car, _)| car).unwrap_or_default())
         }
+        other => Err(HanaError::type_mismatch(
+            "nth",
+            "a list, vector or string",
+            other,
+        )),
     }
+}
 
-    // Copy the body form
-    if let Some(body) = itr.next() {
-        fun.body = body.clone();
+fn handle_len(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "len", 1, Some(1))?;
+
+    match evaluate(args[0].clone(), env)? {
+        Form::List(lst) => Ok(Form::Integer(lst.elements().len() as Integer)),
+        Form::Vector(vector) => Ok(Form::Integer(vector.borrow().len() as Integer)),
+        Form::Set(set) => Ok(Form::Integer(set.len() as Integer)),
+        Form::Str(s) => Ok(Form::Integer(s.chars().count() as Integer)),
+        Form::Nil() => Ok(Form::Integer(0)),
+        other @ Form::Pair(_) => match other.list_elements() {
+            Some(elements) => Ok(Form::Integer(elements.len() as Integer)),
+            None => Err(HanaError::type_mismatch("len", "a proper list", other)),
+        },
+        other => Err(HanaError::type_mismatch(
+            "len",
+            "a list, vector, set or string",
+            other,
+        )),
     }
-
-    // fishes through the body of the function for refs to symbols in
-    // lexical scopes outside it's own, and creates ref. counted smart pointer
-    // clones that get stored in it's own context, effectively closing over that binding.
-    fun.close_over_env(env);
-
-    return Some(Form::Function(fun));
 }
 
-fn handle_car(funcall: &List, env: &mut Environment) -> Option<Form> {
-    if funcall.elements.len() < 2 {
-        println!("Error: function 'car' takes 1 parameter");
-        return Some(Form::Nil());
-    }
-    let mut itr = funcall.elements.iter();
-    itr.next();
-
-    if let Some(itr) = itr.next() {
-        match *itr.clone() {
-            Form::List(_) => {
-                println!("test");
-                let car = evaluate(*itr.clone(), env);
-                match car {
-                    Form::List(car) => {
-                        let c = car.elements.first().unwrap();
-
-                        // return Some(*car.clone());
-                        return Some(*c.clone());
-                    }
-                    _ => {
-                        println!("Error:");
-                    }
-                }
-            }
-            Form::Symbol(_) => {
-                let lstref = evaluate(*itr.clone(), env);
-                let lst = lstref.clone();
-                match lst {
-                    Form::List(lst) => {
-                        if lst.elements.len() == 0 {
-                            return Some(Form::Nil());
-                        }
-
-                        let car = lst.elements.first().unwrap();
-
-                        return Some(*car.clone());
-                    }
-                    _ => {
-                        println!("Error:");
-                    }
-                }
-            }
-            _ => {
-                println!("Error: argument passed to car is not a list.");
-            }
-        }
-    }
+/*
+    (exit status?)
 
-    Some(Form::Nil())
+    Stops evaluation and hands the status code (default: 0) back to the host
+    by unwinding as an error. Only codes from 0 to 255 can be passed to the host.
+*/
+fn handle_exit(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "exit", 0, Some(1))?;
+
+    let code = match args.first() {
+        Some(arg) => match evaluate(arg.clone(), env)? {
+            Form::Integer(code @ 0..=255) => code,
+            code @ (Form::Integer(_) | Form::BigInt(_)) => {
+                return Err(HanaError::invalid_form(
+                    "exit",
+                    &format!("status code {code} is out of range 0-255"),
+                ))
+            }
+            other => return Err(HanaError::type_mismatch("exit", "an integer", other)),
+        },
+        None => 0,
+    };
+
+    Err(HanaError::Exit(code))
 }
 
-fn handle_cdr(funcall: &List, env: &mut Environment) -> Option<Form> {
-    if funcall.elements.len() < 2 {
-        println!("Error: function 'cdr' takes 1 parameter");
-        return Some(Form::Nil());
-    }
-    let mut itr = funcall.elements.iter();
-    itr.next();
-
-    if let Some(itr) = itr.next() {
-        match *itr.clone() {
-            Form::List(_) => {
-                // println!("test");
-                // let (_, cdr) = itr.elements.split_first().unwrap();
-
-                // return Some(Form::List(List {
-                //     elements: cdr.to_vec(),
-                // }));
-
-                let cdr = evaluate(*itr.clone(), env);
-                match cdr {
-                    Form::List(cdr) => {
-                        if cdr.elements.len() <= 1 {
-                            return Some(Form::Nil());
-                        }
-                        let (_, c) = cdr.elements.split_first().unwrap();
-
-                        // return Some(*car.clone());
-                        // return Some(*c.clone());
-
-                        return Some(Form::List(List {
-                            elements: c.to_vec(),
-                        }));
-                    }
-                    _ => {
-                        println!("Error:");
-                    }
-                }
-            }
-            Form::Symbol(_) => {
-                let lstref = evaluate(*itr.clone(), env);
-                let lst = lstref.clone();
-                match lst {
-                    Form::List(lst) => {
-                        if lst.elements.len() <= 1 {
-                            return Some(Form::Nil());
-                        }
-                        let (_, cdr) = lst.elements.split_first().unwrap();
-
-                        return Some(Form::List(List {
-                            elements: cdr.to_vec(),
-                        }));
-                    }
-                    _ => {
-                        println!("Error:");
-                    }
-                }
-            }
-            _ => {
-                println!("Error: argument passed to car is not a list.");
-            }
+/*
+    Evaluates each argument and renders them separated by spaces, either
+    readably (like 'write') or for humans (like 'print').
+*/
+fn render_args(args: &[Form], env: &mut Environment, readably: bool) -> Result<String, HanaError> {
+    let mut out = String::new();
+
+    for (i, arg) in args.iter().enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+        let eval = evaluate(arg.clone(), env)?;
+        if readably {
+            out += &eval.to_string();
+        } else {
+            out += &eval.princ().to_string();
         }
     }
 
-    Some(Form::Nil())
+    Ok(out)
 }
 
-fn create_list(funcall: &List, env: &mut Environment) -> Option<Form> {
-    let mut itr = funcall.elements.iter();
-    itr.next();
+/*
+    (print forms...)
 
-    let mut list = List { elements: vec![] };
+    Writes each form to the environment's output without quotes or escapes.
+*/
+fn handle_print(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "print", 0, None)?;
 
-    while let Some(itr) = itr.next() {
-        let eval = evaluate(*itr.clone(), env);
-        list.elements.push(Box::new(eval));
-    }
+    let out = render_args(args, env, false)?;
+    env.write_output(&out)?;
 
-    Some(Form::List(list))
+    Ok(Form::Nil())
 }
 
-fn handle_nth(funcall: &List, env: &mut Environment) -> Option<Form> {
-    let mut itr = funcall.elements.iter();
-    itr.next();
+// Same as 'print', followed by a newline.
+fn handle_println(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "println", 0, None)?;
 
-    let mut index = -1;
-    if let Some(idx) = itr.next() {
-        match *idx.clone() {
-            Form::Integer(idx) => {
-                index = idx;
-            }
-            _ => {
-                println!("Error: expected type integer in nth");
-            }
-        }
-    }
+    let out = render_args(args, env, false)?;
+    env.write_output(&(out + "\n"))?;
 
-    let mut list = List { elements: vec![] };
-    if let Some(lst) = itr.next() {
-        match *lst.clone() {
-            Form::List(lst) => {
-                list = lst;
-            }
-            Form::Symbol(_) => {
-                let eval = evaluate(*lst.clone(), env);
-                match eval {
-                    Form::List(ev) => {
-                        list = ev;
-                    }
-                    _ => {
-                        println!("Error: symbol passed to nth does not eval to a list.");
-                        return Some(Form::Nil());
-                    }
-                }
-            }
-            _ => {
-                println!("Error: expected type integer in nth");
-                return Some(Form::Nil());
-            }
-        }
-    }
+    Ok(Form::Nil())
+}
 
-    if index < 0 || index as usize >= list.elements.len() {
-        return Some(Form::Nil());
-    }
+/*
+    (write forms...)
+
+    Writes each form to the environment's output as Hana source that can be read back in.
+*/
+fn handle_write(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "write", 0, None)?;
 
-    let thing = list.elements[index as usize].clone();
+    let out = render_args(args, env, true)?;
+    env.write_output(&out)?;
 
-    Some(*thing)
+    Ok(Form::Nil())
 }
 
-fn handle_len(funcall: &List, env: &mut Environment) -> Option<Form> {
-    let mut itr = funcall.elements.iter();
-    itr.next();
+fn handle_newline(funcall: &List, env: &mut Environment) -> HanaResult {
+    expect_args(funcall, "newline", 0, Some(0))?;
 
-    if let Some(itr) = itr.next() {
-        match *itr.clone() {
-            Form::List(itr) => {
-                return Some(Form::Integer(itr.elements.len() as i32));
-            }
-            Form::Symbol(_) => {
-                let eval = evaluate(*itr.clone(), env);
-                match eval {
-                    Form::List(itr) => {
-                        return Some(Form::Integer(itr.elements.len() as i32));
-                    }
-                    _ => {
-                        println!("Error: ");
-                        return Some(Form::Nil());
-                    }
-                }
-            }
-            _ => {
-                println!("Error: cannot call 'len' on non list or string type.");
-                return Some(Form::Nil());
-            }
-        }
-    }
-    return Some(Form::Nil());
-}
+    env.write_output("\n")?;
 
-// fn handle_add(funcall: &List, env: &mut Environment) -> Option<Form> {
-// }
+    Ok(Form::Nil())
+}
@@ -0,0 +1,160 @@
+use std::fmt;
+
+use crate::hana::span::Span;
+use crate::hana::*;
+
+/*
+    Every way that reading or evaluating a form can fail. Errors are handed back
+    to the caller of 'evaluate' rather than being printed, so that a host can tell
+    a legitimate nil result apart from a failed evaluation.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum HanaError {
+    // A symbol was evaluated that has no binding in the current environment.
+    UnboundSymbol(Symbol),
+    // A function was handed a form of the wrong type.
+    TypeMismatch {
+        function: String,
+        expected: &'static str,
+        found: Box<Form>,
+    },
+    // A function was called with the wrong number of arguments.
+    Arity {
+        function: String,
+        expected: String,
+        found: usize,
+    },
+    DivisionByZero,
+    // An index into a string or list was negative or past its end.
+    IndexOutOfRange {
+        function: String,
+        index: BigInt,
+        length: usize,
+    },
+    // The first element of a list being evaluated is not a function.
+    NotCallable(Box<Form>),
+    // A special form was handed something that doesn't fit its syntax,
+    // e.g. a lambda parameter that isn't a symbol.
+    InvalidForm {
+        function: String,
+        reason: String,
+    },
+    // Reading or writing from outside of Hana failed, e.g. the output sink was closed.
+    Io(String),
+    // The grammar rejected the source text.
+    ParseError(String),
+    // Evaluation nested deeper than the environment allows. Holds the names of
+    // the functions that were being called, innermost last.
+    StackOverflow {
+        max_depth: usize,
+        call_stack: Vec<Symbol>,
+    },
+    // Raised by (exit n) to unwind all the way out to the host with a status code.
+    Exit(Integer),
+    // Wraps an error with the location of the innermost form whose evaluation raised it.
+    At(Span, Box<HanaError>),
+}
+
+pub type HanaResult = Result<Form, HanaError>;
+
+impl HanaError {
+    pub fn type_mismatch(function: &str, expected: &'static str, found: Form) -> HanaError {
+        HanaError::TypeMismatch {
+            function: function.to_string(),
+            expected,
+            found: Box::new(found),
+        }
+    }
+
+    pub fn arity(function: &str, expected: &str, found: usize) -> HanaError {
+        HanaError::Arity {
+            function: function.to_string(),
+            expected: expected.to_string(),
+            found,
+        }
+    }
+
+    pub fn invalid_form(function: &str, reason: &str) -> HanaError {
+        HanaError::InvalidForm {
+            function: function.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    // Attaches a source location to the error, unless it already has one
+    // or is an exit request rather than a real failure.
+    pub fn at(self, span: &Option<Span>) -> HanaError {
+        match (self, span) {
+            (err @ (HanaError::At(..) | HanaError::Exit(_)), _) => err,
+            (err, Some(span)) => HanaError::At(span.clone(), Box::new(err)),
+            (err, None) => err,
+        }
+    }
+}
+
+impl fmt::Display for HanaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HanaError::UnboundSymbol(sym) => {
+                write!(f, "symbol '{sym}' is not bound in the current environment")
+            }
+            HanaError::TypeMismatch {
+                function,
+                expected,
+                found,
+            } => write!(f, "'{function}' expected {expected}, found {found}"),
+            HanaError::Arity {
+                function,
+                expected,
+                found,
+            } => write!(
+                f,
+                "'{function}' takes {expected} argument(s), but was given {found}"
+            ),
+            HanaError::DivisionByZero => write!(f, "cannot divide by zero"),
+            HanaError::IndexOutOfRange {
+                function,
+                index,
+                length,
+            } => write!(
+                f,
+                "'{function}' index {index} is out of range for length {length}"
+            ),
+            HanaError::NotCallable(form) => write!(f, "{form} is not a function"),
+            HanaError::InvalidForm { function, reason } => {
+                write!(f, "invalid '{function}' form: {reason}")
+            }
+            HanaError::Io(msg) => write!(f, "i/o error: {msg}"),
+            HanaError::ParseError(msg) => write!(f, "could not parse source\n{msg}"),
+            HanaError::StackOverflow {
+                max_depth,
+                call_stack,
+            } => {
+                write!(
+                    f,
+                    "stack overflow: evaluation nested deeper than {max_depth}"
+                )?;
+                write_call_stack(f, call_stack)
+            }
+            HanaError::Exit(code) => write!(f, "exited with status {code}"),
+            HanaError::At(span, err) => write!(f, "{err}\n{span}"),
+        }
+    }
+}
+
+impl std::error::Error for HanaError {}
+
+// Shows the innermost calls of a call stack, which are the most likely to be recursing.
+fn write_call_stack(f: &mut fmt::Formatter<'_>, call_stack: &[Symbol]) -> fmt::Result {
+    const SHOWN: usize = 10;
+
+    write!(f, "\ncall stack (innermost first):")?;
+    for name in call_stack.iter().rev().take(SHOWN) {
+        write!(f, "\n    {name}")?;
+    }
+    if call_stack.len() > SHOWN {
+        write!(f, "\n    ... {} more", call_stack.len() - SHOWN)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,161 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use crate::hana::builtin::expect_args;
+use crate::hana::*;
+
+/*
+    A keyword is a name written with a leading ':', like :name, that always
+    evaluates to itself. Keywords are interned, so every :name is the same keyword
+    and comparing two keywords only has to compare their pointers.
+*/
+#[derive(Clone)]
+pub struct Keyword(Rc<str>);
+
+thread_local! {
+    static KEYWORDS: RefCell<HashSet<Rc<str>>> = RefCell::new(HashSet::new());
+}
+
+impl Keyword {
+    // Returns the keyword with the given name, which doesn't include the leading ':'.
+    pub fn intern(name: &str) -> Keyword {
+        KEYWORDS.with(|keywords| {
+            let mut keywords = keywords.borrow_mut();
+            match keywords.get(name) {
+                Some(name) => Keyword(Rc::clone(name)),
+                None => {
+                    let name: Rc<str> = Rc::from(name);
+                    keywords.insert(Rc::clone(&name));
+                    Keyword(name)
+                }
+            }
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq for Keyword {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Keyword {}
+
+impl Hash for Keyword {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Rc::as_ptr(&self.0).hash(state);
+    }
+}
+
+impl fmt::Debug for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Keyword({self})")
+    }
+}
+
+impl fmt::Display for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, ":{}", self.0)
+    }
+}
+
+/*
+    (:key map default?)
+
+    A keyword at the head of a list looks itself up in the map that follows it,
+    the same way (get map :key default?) would.
+*/
+pub fn call_keyword(keyword: Keyword, args: &[Form], env: &mut Environment) -> HanaResult {
+    let name = keyword.to_string();
+    if args.is_empty() || args.len() > 2 {
+        return Err(HanaError::arity(&name, "1 or 2", args.len()));
+    }
+
+    let key = Form::Keyword(keyword);
+    let default = match args.get(1) {
+        Some(default) => evaluate(default.clone(), env)?,
+        None => Form::Nil(),
+    };
+
+    match evaluate(args[0].clone(), env)? {
+        Form::Map(map) => Ok(map.get(&key).cloned().unwrap_or(default)),
+        Form::Nil() => Ok(default),
+        other => Err(HanaError::type_mismatch(&name, "a map", other)),
+    }
+}
+
+pub fn handle_is_keyword(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "keyword?", 1, Some(1))?;
+
+    let form = evaluate(args[0].clone(), env)?;
+
+    Ok(Form::Bool(matches!(form, Form::Keyword(_))))
+}
+
+/*
+    (keyword name)
+
+    Returns the keyword with the given name, which can be a string or a symbol.
+    A keyword is returned as it is. The name has to be one that reads back as
+    the same keyword, so it can't be empty or contain anything that ends a token.
+*/
+pub fn handle_keyword(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "keyword", 1, Some(1))?;
+
+    let name = match evaluate(args[0].clone(), env)? {
+        Form::Str(name) => name.to_string(),
+        Form::Symbol(name, _) => name,
+        Form::Keyword(keyword) => return Ok(Form::Keyword(keyword)),
+        other => {
+            return Err(HanaError::type_mismatch(
+                "keyword",
+                "a string or symbol",
+                other,
+            ))
+        }
+    };
+
+    if !is_readable_name(&name) {
+        return Err(HanaError::invalid_form(
+            "keyword",
+            &format!("\"{name}\" is not a valid keyword name"),
+        ));
+    }
+
+    Ok(Form::Keyword(Keyword::intern(&name)))
+}
+
+// Mirrors what the reader accepts after the ':' of a keyword.
+fn is_readable_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_graphic() && !"()[]{}\"'`,;".contains(c))
+}
+
+/*
+    (name keyword)
+
+    Returns the name of a keyword (without the ':') or symbol as a string.
+    A string is returned as it is.
+*/
+pub fn handle_name(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "name", 1, Some(1))?;
+
+    match evaluate(args[0].clone(), env)? {
+        Form::Keyword(keyword) => Ok(Form::Str(Str::from(keyword.name()))),
+        Form::Symbol(name, _) => Ok(Form::Str(Str::from(name.as_str()))),
+        Form::Str(name) => Ok(Form::Str(name)),
+        other => Err(HanaError::type_mismatch(
+            "name",
+            "a keyword, symbol or string",
+            other,
+        )),
+    }
+}
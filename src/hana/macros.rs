@@ -0,0 +1,85 @@
+use crate::hana::builtin::{build_function, expect_args};
+use crate::hana::*;
+
+/*
+    (defmacro name (params...) body)
+
+    Binds a macro to the name in the current scope. A macro is a function
+    (its 'transformer') that's called with the unevaluated forms it was given,
+    and returns the form to evaluate in their place.
+*/
+pub fn def_macro(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "defmacro", 3, Some(3))?;
+
+    let Form::Symbol(name, _) = &args[0] else {
+        return Err(HanaError::type_mismatch(
+            "defmacro",
+            "a symbol",
+            args[0].clone(),
+        ));
+    };
+
+    let transformer = build_function("defmacro", &args[1], &args[2], env)?;
+    env.bind_symbol(name.clone(), Form::Macro(transformer));
+
+    Ok(Form::Nil())
+}
+
+/*
+    Expands the form once if it's a call to a macro bound in the environment.
+    Returns None if the form isn't a macro call.
+*/
+pub fn expand_once(form: &Form, env: &mut Environment) -> Result<Option<Form>, HanaError> {
+    let Form::List(list) = form else {
+        return Ok(None);
+    };
+    let Some((Form::Symbol(head, _), args)) = list.elements().split_first() else {
+        return Ok(None);
+    };
+
+    let Some(found) = env.lookup_symbol(head.clone()) else {
+        return Ok(None);
+    };
+    let Form::Macro(mac) = found.borrow().clone() else {
+        return Ok(None);
+    };
+
+    mac.apply(args.to_vec(), env).map(Some)
+}
+
+/*
+    (macroexpand-1 form)
+
+    Evaluates the argument to get a form, and returns the result of expanding it
+    once if it's a macro call, or the form unchanged otherwise.
+*/
+pub fn handle_macroexpand_1(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "macroexpand-1", 1, Some(1))?;
+
+    let form = evaluate(args[0].clone(), env)?;
+
+    Ok(expand_once(&form, env)?.unwrap_or(form))
+}
+
+/*
+    (macroexpand form)
+
+    Same as macroexpand-1, except the form is expanded over and over
+    until it's no longer a macro call. A macro that keeps expanding into
+    another macro call raises a stack overflow error after 'max_depth' expansions.
+*/
+pub fn handle_macroexpand(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "macroexpand", 1, Some(1))?;
+
+    let mut form = evaluate(args[0].clone(), env)?;
+    let mut expansions = 0;
+    while let Some(expanded) = expand_once(&form, env)? {
+        expansions += 1;
+        if env.depth + expansions > env.max_depth {
+            return Err(env.stack_overflow());
+        }
+        form = expanded;
+    }
+
+    Ok(form)
+}
@@ -0,0 +1,174 @@
+use indexmap::IndexMap;
+
+use crate::hana::builtin::{expect_args, list_or_nil};
+use crate::hana::*;
+
+/*
+    Maps are immutable, so a map is shared between every form that holds it, and
+    the builtins that 'change' a map return a changed copy instead. Entries keep
+    the order they were added in, which is the order they're printed in.
+*/
+pub type Map = Contents<IndexMap<Form, Form>>;
+
+/*
+    Evaluates a map literal, e.g. {:a (+ 1 2)}, by evaluating each key and value
+    in the order they were written.
+*/
+pub fn evaluate_map(map: &Map, env: &mut Environment) -> HanaResult {
+    let mut evaluated = Map::with_capacity(map.len());
+    for (key, value) in map {
+        let key = evaluate(key.clone(), env)?;
+        let value = evaluate(value.clone(), env)?;
+        evaluated.insert(key, value);
+    }
+
+    Ok(Form::Map(Rc::new(evaluated)))
+}
+
+// Nil counts as an empty map, so that (get nil :a) is nil rather than an error.
+fn eval_map(name: &str, form: &Form, env: &mut Environment) -> Result<Rc<Map>, HanaError> {
+    match evaluate(form.clone(), env)? {
+        Form::Map(map) => Ok(map),
+        Form::Nil() => Ok(Rc::new(Map::new())),
+        other => Err(HanaError::type_mismatch(name, "a map", other)),
+    }
+}
+
+/*
+    (get map key default?)
+
+    Returns the value bound to the key, or the default (default: nil)
+    if the map has no such key.
+*/
+pub fn handle_get(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "get", 2, Some(3))?;
+
+    let map = eval_map("get", &args[0], env)?;
+    let key = evaluate(args[1].clone(), env)?;
+    let default = match args.get(2) {
+        Some(default) => evaluate(default.clone(), env)?,
+        None => Form::Nil(),
+    };
+
+    Ok(map.get(&key).cloned().unwrap_or(default))
+}
+
+/*
+    (assoc map key value key value...)
+
+    Returns a copy of the map with each key bound to the value after it.
+*/
+pub fn handle_assoc(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "assoc", 3, None)?;
+
+    if args.len() % 2 == 0 {
+        return Err(HanaError::invalid_form(
+            "assoc",
+            "every key needs a value to go with it",
+        ));
+    }
+
+    let mut map = Rc::unwrap_or_clone(eval_map("assoc", &args[0], env)?);
+    for pair in args[1..].chunks(2) {
+        let key = evaluate(pair[0].clone(), env)?;
+        let value = evaluate(pair[1].clone(), env)?;
+        map.insert(key, value);
+    }
+
+    Ok(Form::Map(Rc::new(map)))
+}
+
+// (dissoc map keys...) returns a copy of the map without the keys.
+pub fn handle_dissoc(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "dissoc", 1, None)?;
+
+    let mut map = Rc::unwrap_or_clone(eval_map("dissoc", &args[0], env)?);
+    for key in &args[1..] {
+        let key = evaluate(key.clone(), env)?;
+        map.shift_remove(&key);
+    }
+
+    Ok(Form::Map(Rc::new(map)))
+}
+
+// (contains? map key) checks for a key in a map, and (contains? set element) for an element in a set.
+pub fn handle_contains(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "contains?", 2, Some(2))?;
+
+    let coll = evaluate(args[0].clone(), env)?;
+    let key = evaluate(args[1].clone(), env)?;
+
+    match coll {
+        Form::Map(map) => Ok(Form::Bool(map.contains_key(&key))),
+        Form::Set(set) => Ok(Form::Bool(set.contains(&key))),
+        Form::Nil() => Ok(Form::Bool(false)),
+        other => Err(HanaError::type_mismatch("contains?", "a map or set", other)),
+    }
+}
+
+pub fn handle_keys(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "keys", 1, Some(1))?;
+
+    let map = eval_map("keys", &args[0], env)?;
+
+    Ok(list_or_nil(map.keys().cloned().collect()))
+}
+
+pub fn handle_vals(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "vals", 1, Some(1))?;
+
+    let map = eval_map("vals", &args[0], env)?;
+
+    Ok(list_or_nil(map.values().cloned().collect()))
+}
+
+/*
+    (merge maps...)
+
+    Returns a map with the entries of every map. When more than one map has
+    the same key, the value from the last of them wins.
+*/
+pub fn handle_merge(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "merge", 0, None)?;
+
+    let mut merged = Map::new();
+    for arg in args {
+        let map = eval_map("merge", arg, env)?;
+        merged.extend(map.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+
+    Ok(Form::Map(Rc::new(merged)))
+}
+
+/*
+    (update map key function args...)
+
+    Returns a copy of the map with the key bound to the result of calling
+    (function old-value args...), where old-value is nil if the key isn't bound.
+*/
+pub fn handle_update(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "update", 3, None)?;
+
+    let mut map = Rc::unwrap_or_clone(eval_map("update", &args[0], env)?);
+    let key = evaluate(args[1].clone(), env)?;
+    let old = map.get(&key).cloned().unwrap_or_default();
+
+    // The function is called through an ordinary call form, so that builtins like
+    // + can be used as well. Every argument is already evaluated, so they're quoted.
+    let mut call = vec![args[2].clone(), quoted(old)];
+    for arg in &args[3..] {
+        call.push(quoted(evaluate(arg.clone(), env)?));
+    }
+    let value = evaluate(Form::List(List::new(call)), env)?;
+
+    map.insert(key, value);
+
+    Ok(Form::Map(Rc::new(map)))
+}
+
+fn quoted(form: Form) -> Form {
+    Form::List(List::new(vec![
+        Form::Symbol("quote".to_string(), None),
+        form,
+    ]))
+}
@@ -0,0 +1,187 @@
+use std::cmp::Ordering;
+
+use num_bigint::BigInt as Big;
+use num_traits::{ToPrimitive, Zero};
+
+use crate::hana::*;
+
+/*
+    The numeric tower. Every numeric form can be viewed as a Number, and arithmetic
+    on numbers follows the usual contagion rules, from the bottom of the tower up:
+
+        Integer -> BigInt -> Ratio -> Real
+
+    Exact numbers (integers, bigints and ratios) stay exact for as long as neither
+    operand is a real, and anything mixed with a real becomes a real. Integers that
+    overflow are promoted to bigints, and exact results are always brought back
+    down to the simplest type that can hold them, so (/ 6 3) is the integer 2.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum Number {
+    Integer(Integer),
+    BigInt(BigInt),
+    Ratio(Ratio),
+    Real(Real),
+}
+
+impl Number {
+    // Views a form as a number, if it is one.
+    pub fn from_form(form: &Form) -> Option<Number> {
+        match form {
+            Form::Integer(i) => Some(Number::Integer(*i)),
+            Form::BigInt(i) => Some(Number::BigInt(i.clone())),
+            Form::Ratio(r) => Some(Number::Ratio(r.clone())),
+            Form::Real(r) => Some(Number::Real(*r)),
+            _ => None,
+        }
+    }
+
+    pub fn into_form(self) -> Form {
+        match self {
+            Number::Integer(i) => Form::Integer(i),
+            Number::BigInt(i) => Form::BigInt(i),
+            Number::Ratio(r) => Form::Ratio(r),
+            Number::Real(r) => Form::Real(r),
+        }
+    }
+
+    pub fn to_real(&self) -> Real {
+        match self {
+            Number::Integer(i) => *i as Real,
+            Number::BigInt(i) => i.to_f64().unwrap_or(Real::NAN),
+            Number::Ratio(r) => r.to_f64().unwrap_or(Real::NAN),
+            Number::Real(r) => *r,
+        }
+    }
+
+    // Returns the number as an exact ratio, or None if it's a real.
+    fn to_ratio(&self) -> Option<Ratio> {
+        match self {
+            Number::Integer(i) => Some(Ratio::from_integer(Big::from(*i))),
+            Number::BigInt(i) => Some(Ratio::from_integer(i.clone())),
+            Number::Ratio(r) => Some(r.clone()),
+            Number::Real(_) => None,
+        }
+    }
+
+    // Returns the number as a bigint, or None if it isn't a whole exact number.
+    fn to_bigint(&self) -> Option<BigInt> {
+        match self {
+            Number::Integer(i) => Some(Big::from(*i)),
+            Number::BigInt(i) => Some(i.clone()),
+            _ => None,
+        }
+    }
+
+    // Brings a bigint down to an integer when it fits in one.
+    pub fn from_bigint(i: BigInt) -> Number {
+        match i.to_i32() {
+            Some(i) => Number::Integer(i),
+            None => Number::BigInt(i),
+        }
+    }
+
+    // Brings a ratio down to an integer or bigint when it's a whole number.
+    pub fn from_ratio(r: Ratio) -> Number {
+        match r.is_integer() {
+            true => Number::from_bigint(r.to_integer()),
+            false => Number::Ratio(r),
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        match self {
+            Number::Integer(i) => *i == 0,
+            Number::BigInt(i) => i.is_zero(),
+            Number::Ratio(r) => r.is_zero(),
+            Number::Real(r) => *r == 0.0,
+        }
+    }
+
+    pub fn add(self, rhs: Number) -> Number {
+        apply(
+            self,
+            rhs,
+            Integer::checked_add,
+            |a, b| a + b,
+            |a, b| a + b,
+            |a, b| a + b,
+        )
+    }
+
+    pub fn sub(self, rhs: Number) -> Number {
+        apply(
+            self,
+            rhs,
+            Integer::checked_sub,
+            |a, b| a - b,
+            |a, b| a - b,
+            |a, b| a - b,
+        )
+    }
+
+    pub fn mul(self, rhs: Number) -> Number {
+        apply(
+            self,
+            rhs,
+            Integer::checked_mul,
+            |a, b| a * b,
+            |a, b| a * b,
+            |a, b| a * b,
+        )
+    }
+
+    /*
+        Dividing by zero is an error. Dividing two exact numbers always gives an
+        exact result, which is a ratio unless the division leaves no remainder.
+    */
+    pub fn div(self, rhs: Number) -> Result<Number, HanaError> {
+        if rhs.is_zero() {
+            return Err(HanaError::DivisionByZero);
+        }
+
+        match (self.to_ratio(), rhs.to_ratio()) {
+            (Some(a), Some(b)) => Ok(Number::from_ratio(a / b)),
+            _ => Ok(Number::Real(self.to_real() / rhs.to_real())),
+        }
+    }
+
+    // Exact numbers are compared exactly, anything involving a real is compared as reals.
+    pub fn compare(&self, rhs: &Number) -> Option<Ordering> {
+        match (self, rhs) {
+            (Number::Integer(a), Number::Integer(b)) => Some(a.cmp(b)),
+            _ => match (self.to_ratio(), rhs.to_ratio()) {
+                (Some(a), Some(b)) => Some(a.cmp(&b)),
+                _ => self.to_real().partial_cmp(&rhs.to_real()),
+            },
+        }
+    }
+}
+
+/*
+    Applies an operator at the lowest level of the tower both operands fit in,
+    falling back to bigints when integer arithmetic overflows.
+*/
+fn apply(
+    lhs: Number,
+    rhs: Number,
+    int_op: fn(Integer, Integer) -> Option<Integer>,
+    big_op: fn(BigInt, BigInt) -> BigInt,
+    ratio_op: fn(Ratio, Ratio) -> Ratio,
+    real_op: fn(Real, Real) -> Real,
+) -> Number {
+    if let (Number::Integer(a), Number::Integer(b)) = (&lhs, &rhs) {
+        if let Some(i) = int_op(*a, *b) {
+            return Number::Integer(i);
+        }
+    }
+
+    if let (Some(a), Some(b)) = (lhs.to_bigint(), rhs.to_bigint()) {
+        return Number::from_bigint(big_op(a, b));
+    }
+
+    match (lhs.to_ratio(), rhs.to_ratio()) {
+        (Some(a), Some(b)) => Number::from_ratio(ratio_op(a, b)),
+        _ => Number::Real(real_op(lhs.to_real(), rhs.to_real())),
+    }
+}
@@ -0,0 +1,133 @@
+use std::borrow::Cow;
+
+use crate::hana::builtin::expect_args;
+use crate::hana::*;
+
+/*
+    A cons cell, made by (cons car cdr). A chain of pairs ending in nil is a
+    proper list, and is equal to (and prints the same as) a list with the same
+    elements. Any other cdr makes an improper list, which prints as (a b . c).
+
+    Consing onto a list never copies it: the new pair just holds on to the list
+    as its cdr, so every list consed onto the same tail shares it.
+*/
+#[derive(Debug)]
+pub struct Cons {
+    pub car: Form,
+    pub cdr: Form,
+}
+
+// Like lists, pairs are dropped without recursing, so that a long chain of them can't overflow the stack.
+impl Drop for Cons {
+    fn drop(&mut self) {
+        if self.car.holds_forms() || self.cdr.holds_forms() {
+            drop_nested(vec![
+                std::mem::take(&mut self.car),
+                std::mem::take(&mut self.cdr),
+            ]);
+        }
+    }
+}
+
+// The empty list is stored as nil, so that a pair chain always ends in nil or a non-empty list.
+pub fn cons(car: Form, cdr: Form) -> Form {
+    let cdr = match cdr {
+        Form::List(list) if list.elements().is_empty() => Form::Nil(),
+        cdr => cdr,
+    };
+
+    Form::Pair(Rc::new(Cons { car, cdr }))
+}
+
+impl Form {
+    // Whether the form holds other forms, which might hold others in turn.
+    pub fn holds_forms(&self) -> bool {
+        matches!(
+            self,
+            Form::List(_) | Form::Pair(_) | Form::Vector(_) | Form::Map(_) | Form::Set(_)
+        )
+    }
+
+    /*
+        Splits a pair or non-empty list into its first element and the rest of it.
+        The rest of a one-element list is nil. Anything else can't be split.
+    */
+    pub fn uncons(&self) -> Option<(Form, Form)> {
+        match self {
+            Form::Pair(pair) => Some((pair.car.clone(), pair.cdr.clone())),
+            Form::List(list) => {
+                let (first, rest) = list.elements().split_first()?;
+                let rest = match rest.is_empty() {
+                    true => Form::Nil(),
+                    false => Form::List(list.rest()),
+                };
+                Some((first.clone(), rest))
+            }
+            _ => None,
+        }
+    }
+
+    /*
+        Returns the elements of a proper list: nil, a list, or a chain of pairs that
+        ends in either of them. Only a chain of pairs has to be copied to do this.
+    */
+    pub fn list_elements(&self) -> Option<Cow<'_, [Form]>> {
+        let mut elements = Vec::new();
+        let mut tail = match self {
+            Form::Nil() => return Some(Cow::Borrowed(&[])),
+            Form::List(list) => return Some(Cow::Borrowed(list.elements())),
+            Form::Pair(_) => self.clone(),
+            _ => return None,
+        };
+
+        while let Form::Pair(pair) = tail {
+            elements.push(pair.car.clone());
+            tail = pair.cdr.clone();
+        }
+
+        match tail {
+            Form::Nil() => Some(Cow::Owned(elements)),
+            Form::List(list) => {
+                elements.extend_from_slice(list.elements());
+                Some(Cow::Owned(elements))
+            }
+            _ => None,
+        }
+    }
+}
+
+/*
+    (cons car cdr)
+
+    Returns a new pair of the two forms. When the cdr is a list, this is
+    the list with car added to the front of it.
+*/
+pub fn handle_cons(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "cons", 2, Some(2))?;
+
+    let car = evaluate(args[0].clone(), env)?;
+    let cdr = evaluate(args[1].clone(), env)?;
+
+    Ok(cons(car, cdr))
+}
+
+// (pair? form) is true for pairs and non-empty lists, i.e. anything with a car and cdr.
+pub fn handle_is_pair(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "pair?", 1, Some(1))?;
+
+    let form = evaluate(args[0].clone(), env)?;
+
+    Ok(Form::Bool(form.uncons().is_some()))
+}
+
+// (list? form) is true for proper lists, including nil, but not for improper ones like (1 . 2).
+pub fn handle_is_list(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "list?", 1, Some(1))?;
+
+    let mut tail = evaluate(args[0].clone(), env)?;
+    while let Form::Pair(pair) = tail {
+        tail = pair.cdr.clone();
+    }
+
+    Ok(Form::Bool(matches!(tail, Form::Nil() | Form::List(_))))
+}
@@ -0,0 +1,270 @@
+use std::fmt;
+
+use crate::hana::*;
+
+/*
+    The 'Printer' for Hana. Forms can be printed in one of two ways, mirroring
+    Common Lisp's prin1 and princ:
+
+    - Display renders the form 'readably', as Hana source that reads back
+        into an equal form, e.g. (1 "a \"b\"\n" 2.0).
+    - Form::princ renders it for humans, where strings are written out as-is
+        without quotes or escapes, e.g. (1 a "b" 2.0).
+*/
+impl fmt::Display for Form {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_form(f, self, true)
+    }
+}
+
+impl fmt::Display for List {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_form(f, &Form::List(self.clone()), true)
+    }
+}
+
+pub struct Princ<'a>(&'a Form);
+
+impl fmt::Display for Princ<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_form(f, self.0, false)
+    }
+}
+
+impl Form {
+    pub fn princ(&self) -> Princ<'_> {
+        Princ(self)
+    }
+}
+
+// What's left to print: either a form, or the text between and after its elements.
+enum Item {
+    Form(Form),
+    Text(&'static str),
+}
+
+/*
+    Rather than recursing into each element, the elements still to be printed are kept
+    on a stack of their own, so that printing a deeply nested form can't overflow the stack.
+*/
+fn write_form(f: &mut fmt::Formatter<'_>, form: &Form, readably: bool) -> fmt::Result {
+    let mut pending = vec![Item::Form(form.clone())];
+
+    while let Some(item) = pending.pop() {
+        match item {
+            Item::Text(text) => f.write_str(text)?,
+            Item::Form(form) => write_shallow(f, &form, readably, &mut pending)?,
+        }
+    }
+
+    Ok(())
+}
+
+// Writes the form up to its first element, pushing its elements and whatever follows them.
+fn write_shallow(
+    f: &mut fmt::Formatter<'_>,
+    form: &Form,
+    readably: bool,
+    pending: &mut Vec<Item>,
+) -> fmt::Result {
+    match form {
+        Form::Integer(i) => write!(f, "{i}"),
+        Form::BigInt(i) => write!(f, "{i}"),
+        Form::Ratio(r) => write!(f, "{r}"),
+        Form::Real(r) => write_real(f, *r),
+        Form::Str(s) if readably => write_escaped(f, s),
+        Form::Str(s) => write!(f, "{s}"),
+        Form::Char(c) if readably => write_char(f, *c),
+        Form::Char(c) => write!(f, "{c}"),
+        Form::Bool(b) => write!(f, "{b}"),
+        Form::Symbol(s, _) => write!(f, "{s}"),
+        Form::Keyword(k) => write!(f, "{k}"),
+        Form::List(list) => {
+            push_elements(pending, list.elements().to_vec(), ")");
+            write!(f, "(")
+        }
+        Form::Pair(_) => {
+            push_pair(pending, form);
+            write!(f, "(")
+        }
+        Form::Vector(vector) => {
+            push_elements(pending, vector.borrow().to_vec(), "]");
+            write!(f, "[")
+        }
+        Form::Map(map) => {
+            let entries = map
+                .iter()
+                .flat_map(|(key, value)| [key.clone(), value.clone()]);
+            push_elements(pending, entries.collect(), "}");
+            write!(f, "{{")
+        }
+        Form::Set(set) => {
+            push_elements(pending, set.iter().cloned().collect(), "}");
+            write!(f, "#{{")
+        }
+        Form::Function(fun) => {
+            pending.push(Item::Text(">"));
+            push_elements(pending, fun.params.clone(), ")");
+            write!(f, "#<function (")
+        }
+        Form::Macro(mac) => {
+            pending.push(Item::Text(">"));
+            push_elements(pending, mac.params.clone(), ")");
+            write!(f, "#<macro (")
+        }
+        Form::Nil() => write!(f, "nil"),
+    }
+}
+
+// Pushes the elements so that they're printed in order, separated by spaces, followed by 'close'.
+fn push_elements(pending: &mut Vec<Item>, elements: Vec<Form>, close: &'static str) {
+    pending.push(Item::Text(close));
+    for (i, elem) in elements.into_iter().enumerate().rev() {
+        pending.push(Item::Form(elem));
+        if i > 0 {
+            pending.push(Item::Text(" "));
+        }
+    }
+}
+
+/*
+    Follows the chain of pairs for as long as it goes, so a proper list prints
+    as (a b c) however it was built, and an improper one as (a b . c).
+*/
+fn push_pair(pending: &mut Vec<Item>, pair: &Form) {
+    let mut elements = vec![];
+    let mut tail = pair.clone();
+    while let Form::Pair(next) = tail {
+        elements.push(next.car.clone());
+        tail = next.cdr.clone();
+    }
+
+    match tail {
+        Form::Nil() => push_elements(pending, elements, ")"),
+        Form::List(list) => {
+            elements.extend_from_slice(list.elements());
+            push_elements(pending, elements, ")");
+        }
+        other => {
+            pending.push(Item::Text(")"));
+            pending.push(Item::Form(other));
+            push_elements(pending, elements, " . ");
+        }
+    }
+}
+
+// Reals always keep a decimal point or exponent, so that they don't read back as integers.
+fn write_real(f: &mut fmt::Formatter<'_>, r: Real) -> fmt::Result {
+    if r.is_nan() {
+        write!(f, "nan")
+    } else if r.is_infinite() {
+        write!(f, "{}inf", if r < 0.0 { "-" } else { "" })
+    } else {
+        // Debug keeps the ".0" on whole numbers and switches to an exponent for very
+        // large or small ones, e.g. 1.0, 0.1 and 1e300, all of which read back the same
+        write!(f, "{r:?}")
+    }
+}
+
+fn write_char(f: &mut fmt::Formatter<'_>, c: char) -> fmt::Result {
+    match CHAR_NAMES.iter().find(|(_, named)| *named == c) {
+        Some((name, _)) => write!(f, "#\\{name}"),
+        None if c.is_control() => write!(f, "#\\x{:x}", c as u32),
+        None => write!(f, "#\\{c}"),
+    }
+}
+
+fn write_escaped(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\t' => write!(f, "\\t")?,
+            '\r' => write!(f, "\\r")?,
+            '\0' => write!(f, "\\0")?,
+            c if c.is_control() => write!(f, "\\u{{{:x}}}", c as u32)?,
+            _ => write!(f, "{c}")?,
+        }
+    }
+    write!(f, "\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hana::pair::cons;
+    use crate::hana::vector::new_vector;
+
+    fn sym(name: &str) -> Form {
+        Form::Symbol(name.to_string(), None)
+    }
+
+    fn kw(name: &str) -> Form {
+        Form::Keyword(Keyword::intern(name))
+    }
+
+    fn ratio(numer: i64, denom: i64) -> Form {
+        Form::Ratio(Ratio::new(numer.into(), denom.into()))
+    }
+
+    // Printing a form readably and reading it back must give an equal form.
+    fn assert_round_trips(form: Form) {
+        let text = form.to_string();
+        let read = parse(&text).unwrap_or_else(|e| panic!("{text} didn't read back: {e}"));
+
+        assert_eq!(read, vec![form], "{text} read back differently");
+    }
+
+    #[test]
+    fn forms_round_trip() {
+        for r in [1e300, 1e-300, 0.1, 1.0, -2.5, 1.0 / 3.0, 1e21] {
+            assert_round_trips(Form::Real(r));
+        }
+        assert_round_trips(Form::Real(Real::INFINITY));
+        assert_round_trips(Form::Real(Real::NEG_INFINITY));
+
+        assert_round_trips(ratio(1, 3));
+        assert_round_trips(ratio(-7, 2));
+        assert_round_trips(Form::BigInt(BigInt::from(2).pow(100)));
+
+        assert_round_trips(Form::Str(Str::from("a \"b\"\n\t\r\0\u{7}\\ λ")));
+        for c in [' ', '\n', '\t', '\0', '\u{7}', 'a', 'λ', '(', ';'] {
+            assert_round_trips(Form::Char(c));
+        }
+
+        assert_round_trips(kw("key"));
+        assert_round_trips(kw("a-b?"));
+
+        assert_round_trips(cons(Form::Integer(1), Form::Integer(2)));
+        assert_round_trips(cons(
+            Form::Integer(1),
+            cons(Form::Str(Str::from("two")), Form::Char('3')),
+        ));
+        assert_round_trips(cons(sym("a"), Form::List(List::new(vec![sym("b")]))));
+
+        let list = Form::List(List::new(vec![
+            sym("f"),
+            Form::Integer(-1),
+            Form::Real(0.5),
+            Form::Bool(true),
+            Form::Nil(),
+        ]));
+        let vector = new_vector(vec![Form::Integer(1), list.clone(), new_vector(vec![])]);
+        let map = Form::Map(Rc::new(Map::from_iter([
+            (kw("a"), Form::Integer(1)),
+            (Form::Str(Str::from("b")), vector.clone()),
+        ])));
+        let set = Form::Set(Rc::new(Set::from_iter([
+            Form::Integer(1),
+            Form::Str(Str::from("x")),
+            map.clone(),
+        ])));
+
+        assert_round_trips(list);
+        assert_round_trips(vector);
+        assert_round_trips(map);
+        assert_round_trips(set);
+    }
+}
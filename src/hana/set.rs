@@ -0,0 +1,152 @@
+use indexmap::IndexSet;
+
+use crate::hana::builtin::expect_args;
+use crate::hana::*;
+
+/*
+    Sets are immutable like maps, and use the same structural hashing, so any
+    form can be an element. Elements keep the order they were added in, which
+    is the order they're printed and iterated in.
+*/
+pub type Set = Contents<IndexSet<Form>>;
+
+/*
+    Evaluates a set literal, e.g. #{1 (+ 1 1)}, by evaluating each element in order.
+    Elements that turn out to be equal once they're evaluated are only kept once.
+*/
+pub fn evaluate_set(set: &Set, env: &mut Environment) -> HanaResult {
+    let mut evaluated = Set::with_capacity(set.len());
+    for elem in set {
+        evaluated.insert(evaluate(elem.clone(), env)?);
+    }
+
+    Ok(Form::Set(Rc::new(evaluated)))
+}
+
+// Like maps, nil counts as an empty set.
+fn eval_set(name: &str, form: &Form, env: &mut Environment) -> Result<Rc<Set>, HanaError> {
+    match evaluate(form.clone(), env)? {
+        Form::Set(set) => Ok(set),
+        Form::Nil() => Ok(Rc::new(Set::new())),
+        other => Err(HanaError::type_mismatch(name, "a set", other)),
+    }
+}
+
+// Evaluates every argument as a set, for the functions that combine sets.
+fn eval_sets(name: &str, args: &[Form], env: &mut Environment) -> Result<Vec<Rc<Set>>, HanaError> {
+    args.iter().map(|arg| eval_set(name, arg, env)).collect()
+}
+
+// (hash-set elements...) returns a new set holding the elements.
+pub fn handle_hash_set(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "hash-set", 0, None)?;
+
+    let mut set = Set::with_capacity(args.len());
+    for arg in args {
+        set.insert(evaluate(arg.clone(), env)?);
+    }
+
+    Ok(Form::Set(Rc::new(set)))
+}
+
+/*
+    (list->set collection)
+
+    Returns a set of the elements of a list, vector, set, or string (as characters),
+    dropping any duplicates.
+*/
+pub fn handle_list_to_set(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "list->set", 1, Some(1))?;
+
+    let set = match evaluate(args[0].clone(), env)? {
+        Form::Set(set) => return Ok(Form::Set(set)),
+        Form::Vector(vector) => vector.borrow().iter().cloned().collect(),
+        Form::Str(s) => s.chars().map(Form::Char).collect(),
+        seq => match seq.list_elements() {
+            Some(elements) => elements.iter().cloned().collect(),
+            None => {
+                return Err(HanaError::type_mismatch(
+                    "list->set",
+                    "a list, vector, set or string",
+                    seq,
+                ))
+            }
+        },
+    };
+
+    Ok(Form::Set(Rc::new(set)))
+}
+
+// (conj set elements...) returns a copy of the set with the elements added.
+pub fn handle_conj(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "conj", 1, None)?;
+
+    let mut set = Rc::unwrap_or_clone(eval_set("conj", &args[0], env)?);
+    for arg in &args[1..] {
+        set.insert(evaluate(arg.clone(), env)?);
+    }
+
+    Ok(Form::Set(Rc::new(set)))
+}
+
+// (disj set elements...) returns a copy of the set without the elements.
+pub fn handle_disj(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "disj", 1, None)?;
+
+    let mut set = Rc::unwrap_or_clone(eval_set("disj", &args[0], env)?);
+    for arg in &args[1..] {
+        set.shift_remove(&evaluate(arg.clone(), env)?);
+    }
+
+    Ok(Form::Set(Rc::new(set)))
+}
+
+// (union sets...) returns a set with the elements of every set.
+pub fn handle_union(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "union", 0, None)?;
+
+    let mut union = Set::new();
+    for set in eval_sets("union", args, env)? {
+        union.extend(set.iter().cloned());
+    }
+
+    Ok(Form::Set(Rc::new(union)))
+}
+
+// (intersection set sets...) returns a set with the elements that are in every set.
+pub fn handle_intersection(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "intersection", 1, None)?;
+
+    let sets = eval_sets("intersection", args, env)?;
+    let intersection = sets[0]
+        .iter()
+        .filter(|elem| sets[1..].iter().all(|set| set.contains(*elem)))
+        .cloned()
+        .collect();
+
+    Ok(Form::Set(Rc::new(intersection)))
+}
+
+// (difference set sets...) returns a set with the elements of the first set that aren't in any of the others.
+pub fn handle_difference(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "difference", 1, None)?;
+
+    let sets = eval_sets("difference", args, env)?;
+    let difference = sets[0]
+        .iter()
+        .filter(|elem| !sets[1..].iter().any(|set| set.contains(*elem)))
+        .cloned()
+        .collect();
+
+    Ok(Form::Set(Rc::new(difference)))
+}
+
+// (subset? set other) is true when every element of the set is also in the other set.
+pub fn handle_is_subset(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "subset?", 2, Some(2))?;
+
+    let set = eval_set("subset?", &args[0], env)?;
+    let other = eval_set("subset?", &args[1], env)?;
+
+    Ok(Form::Bool(set.is_subset(&other)))
+}
@@ -0,0 +1,102 @@
+use std::fmt;
+use std::rc::Rc;
+
+/*
+    A chunk of source text that forms are read from, along with the name
+    it should be reported under (usually a file path).
+*/
+#[derive(Debug, PartialEq)]
+pub struct Source {
+    pub name: String,
+    pub text: String,
+}
+
+/*
+    Marks the byte range of a source that a form was read from. Spans are
+    kept on list and symbol forms so that errors raised while evaluating
+    them can point back at the offending code.
+*/
+#[derive(Clone)]
+pub struct Span {
+    pub source: Rc<Source>,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(source: &Rc<Source>, span: pest::Span) -> Span {
+        Span {
+            source: Rc::clone(source),
+            start: span.start(),
+            end: span.end(),
+        }
+    }
+
+    // Returns the 1-based line and column that the span starts on.
+    pub fn line_col(&self) -> (usize, usize) {
+        let before = &self.source.text[..self.start];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let col = before[line_start..].chars().count() + 1;
+
+        (line, col)
+    }
+
+    // Returns the full text of the line that the span starts on.
+    pub fn line_text(&self) -> &str {
+        let text = &self.source.text;
+        let line_start = text[..self.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = text[self.start..]
+            .find('\n')
+            .map_or(text.len(), |i| self.start + i);
+
+        text[line_start..line_end].trim_end_matches('\r')
+    }
+}
+
+impl PartialEq for Span {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.source, &other.source)
+            && self.start == other.start
+            && self.end == other.end
+    }
+}
+
+impl fmt::Debug for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (line, col) = self.line_col();
+        write!(f, "Span({}:{line}:{col})", self.source.name)
+    }
+}
+
+/*
+    Renders the span the same way pest renders grammar errors:
+
+     --> file:line:col
+      |
+    3 | (car 1)
+      | ^-----^
+*/
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (line, col) = self.line_col();
+        let text = self.line_text();
+        let gutter = " ".repeat(line.to_string().len());
+
+        // only underline up to the end of the first line of the span
+        let remaining = text.chars().count().saturating_sub(col - 1);
+        let width = self.source.text[self.start..self.end]
+            .chars()
+            .count()
+            .clamp(1, remaining.max(1));
+        let underline = match width {
+            1 => "^".to_string(),
+            _ => format!("^{}^", "-".repeat(width - 2)),
+        };
+
+        writeln!(f, "{gutter}--> {}:{line}:{col}", self.source.name)?;
+        writeln!(f, "{gutter} |")?;
+        writeln!(f, "{line} | {text}")?;
+        write!(f, "{gutter} | {}{underline}", " ".repeat(col - 1))
+    }
+}
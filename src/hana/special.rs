@@ -1,40 +1,47 @@
-use std::any::Any;
-
+use crate::hana::builtin::expect_args;
 use crate::hana::*;
 
-pub fn handle_if(funcall: &List, env: &mut Environment) -> Option<Form> {
-    let mut itr = funcall.elements.iter();
-    itr.next();
-
-    let conditional = itr.next();
-
-    if let Some(conditional) = conditional {
-        let eval = evaluate(*conditional.clone(), env);
-        match eval {
-            Form::Bool(eval) => {
-                if eval == true {
-                    let case = itr
-                        .next()
-                        .expect("Error: could not find form for the true case of if stmt.");
-                    return Some(evaluate(*case.clone(), env));
-                } else {
-                    itr.next();
-                    let case = itr
-                        .next()
-                        .expect("Error: could not find form for the false case of if stmt.");
-                    return Some(evaluate(*case.clone(), env));
-                }
-            }
-            _ => {
-                println!(
-                    "Error: conditional form passed to 'if' must evaluate to a boolean expression."
-                );
-                return Some(Form::Nil());
-            }
+// Takes refs to a symbol and the current environment, and compares the symbol
+// against the special forms that end in a form in tail position. Rather than
+// evaluating that last form themselves, they hand it back to the evaluator.
+pub fn tail_special_form(
+    symbol: &Symbol,
+    funcall: &List,
+    env: &mut Environment,
+) -> Option<Result<Tail, HanaError>> {
+    let result = match symbol.as_str() {
+        "if" => handle_if(funcall, env),
+        "progn" => handle_progn(funcall, env),
+        "let" => handle_let(funcall, env),
+        _ => {
+            return None;
         }
-    }
+    };
 
-    return Some(Form::Nil());
+    Some(result)
+}
+
+/*
+    (if conditional true-form false-form?)
+
+    Evaluates the conditional, and then evaluates only the form matching the result.
+    If the conditional is false and no false-form is given, the result is nil.
+*/
+pub fn handle_if(funcall: &List, env: &mut Environment) -> Result<Tail, HanaError> {
+    let args = expect_args(funcall, "if", 2, Some(3))?;
+
+    match evaluate(args[0].clone(), env)? {
+        Form::Bool(true) => Ok(Tail::Eval(args[1].clone())),
+        Form::Bool(false) => match args.get(2) {
+            Some(case) => Ok(Tail::Eval(case.clone())),
+            None => Ok(Tail::Return(Form::Nil())),
+        },
+        other => Err(HanaError::type_mismatch(
+            "if",
+            "a boolean conditional",
+            other,
+        )),
+    }
 }
 
 /*
@@ -43,87 +50,188 @@ pub fn handle_if(funcall: &List, env: &mut Environment) -> Option<Form> {
     evaluated, the evaluator will consume the "wrapping" quote,
     returning the form that was initially quoted.
 */
+pub fn handle_quote(funcall: &List, _env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "quote", 1, Some(1))?;
+
+    Ok(args[0].clone())
+}
+
+/*
+    (quasiquote form), or `form
+
+    Works like quote, except that any (unquote x) inside of the form, written ,x
+    is replaced by the result of evaluating x, and any (unquote-splicing x), written ,@x
+    has the list x evaluates to spliced into the list, vector or set it appears in.
+    The keys and values of a map are expanded too.
+
+    Quasiquotes can be nested, in which case an unquote only belongs to the innermost
+    quasiquote, and is left in place (with its own contents expanded) for the outer ones.
+*/
+pub fn handle_quasiquote(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "quasiquote", 1, Some(1))?;
 
-#[allow(dead_code)]
-pub fn handle_quote(funcall: &List, _env: &mut Environment) -> Option<Form> {
-    if funcall.elements.len() != 2 {
-        println!("Error: special form 'quote' takes only a single form as parameter.");
-        return Some(Form::Nil());
+    quasiquote(&args[0], 1, env)
+}
+
+// Returns the argument of a (name arg) form, if the form is one.
+fn quoted_arg<'a>(form: &'a Form, name: &str) -> Option<&'a Form> {
+    match form {
+        Form::List(list) => match list.elements() {
+            [Form::Symbol(sym, _), arg] if sym == name => Some(arg),
+            _ => None,
+        },
+        _ => None,
     }
+}
+
+// Expands a quasiquoted form, where depth is how many quasiquotes deep the form is.
+fn quasiquote(form: &Form, depth: usize, env: &mut Environment) -> HanaResult {
+    let list = match form {
+        Form::List(list) => list,
+        Form::Pair(_) => return quasiquote_pair(form, depth, env),
+        Form::Vector(vector) => {
+            // like a vector literal, every expansion makes a new vector
+            let elements = vector.borrow().to_vec();
+            let elements = quasiquote_elements(&elements, depth, env)?;
+            return Ok(vector::new_vector(elements));
+        }
+        Form::Set(set) => {
+            let elements: Vec<Form> = set.iter().cloned().collect();
+            let elements = quasiquote_elements(&elements, depth, env)?;
+            return Ok(Form::Set(Rc::new(elements.into_iter().collect())));
+        }
+        Form::Map(map) => return quasiquote_map(map, depth, env),
+        _ => return Ok(form.clone()),
+    };
 
-    let mut itr = funcall.elements.iter();
-    itr.next();
-    if let Some(quoted) = itr.next() {
-        // println!("TEST: {quoted:?}");
-        return Some(*quoted.clone());
+    if let Some(arg) = quoted_arg(form, "unquote") {
+        return match depth {
+            1 => evaluate(arg.clone(), env),
+            _ => requote("unquote", arg, depth - 1, list, env),
+        };
+    }
+    if let Some(arg) = quoted_arg(form, "unquote-splicing") {
+        return match depth {
+            1 => Err(HanaError::invalid_form(
+                "unquote-splicing",
+                "can only be used inside of a list, vector or set",
+            )),
+            _ => requote("unquote-splicing", arg, depth - 1, list, env),
+        };
+    }
+    if let Some(arg) = quoted_arg(form, "quasiquote") {
+        return requote("quasiquote", arg, depth + 1, list, env);
     }
 
-    Some(Form::Nil())
+    let elements = quasiquote_elements(list.elements(), depth, env)?;
+
+    Ok(Form::List(List::with_span(elements, list.span.clone())))
 }
 
-pub fn def_symbol(funcall: &List, env: &mut Environment) -> Option<Form> {
-    if funcall.elements.len() < 3 {
-        println!("Error: function 'def' takes >= 2 parameters");
-        return Some(Form::Nil());
+// Expands each element in turn, splicing in the elements of any (unquote-splicing x).
+fn quasiquote_elements(
+    forms: &[Form],
+    depth: usize,
+    env: &mut Environment,
+) -> Result<Vec<Form>, HanaError> {
+    let mut elements = Vec::with_capacity(forms.len());
+    for elem in forms {
+        match quoted_arg(elem, "unquote-splicing") {
+            Some(arg) if depth == 1 => {
+                let spliced = evaluate(arg.clone(), env)?;
+                match spliced.list_elements() {
+                    Some(items) => elements.extend_from_slice(&items),
+                    None => {
+                        return Err(HanaError::type_mismatch(
+                            "unquote-splicing",
+                            "a list",
+                            spliced,
+                        ))
+                    }
+                }
+            }
+            _ => elements.push(quasiquote(elem, depth, env)?),
+        }
     }
 
-    let sym = *funcall.elements[1].clone();
-    let value = *funcall.elements[2].clone();
+    Ok(elements)
+}
 
-    match sym {
-        Form::Symbol(sym) => {
-            let evaluated = evaluate(value, env);
-            env.bind_symbol(sym, evaluated);
-        }
-        _ => {}
+/*
+    Expands a dotted list like (a b . c). The form after the dot is expanded on its
+    own, so `(a . ,x) makes x the tail of the list, whatever it evaluates to.
+*/
+fn quasiquote_pair(form: &Form, depth: usize, env: &mut Environment) -> HanaResult {
+    let mut cars = vec![];
+    let mut tail = form.clone();
+    while let Form::Pair(pair) = &tail {
+        cars.push(pair.car.clone());
+        tail = pair.cdr.clone();
     }
 
-    return Some(Form::Nil());
+    let elements = quasiquote_elements(&cars, depth, env)?;
+    let tail = quasiquote(&tail, depth, env)?;
+
+    Ok(elements
+        .into_iter()
+        .rev()
+        .fold(tail, |cdr, car| pair::cons(car, cdr)))
 }
 
-pub fn set_symbol(funcall: &List, env: &mut Environment) -> Option<Form> {
-    if funcall.elements.len() < 3 {
-        println!("Error: function 'def' takes >= 2 parameters");
-        return Some(Form::Nil());
+// Expands every key and value of a map, in the order they were written.
+fn quasiquote_map(map: &Map, depth: usize, env: &mut Environment) -> HanaResult {
+    let mut expanded = Map::with_capacity(map.len());
+    for (key, value) in map {
+        let key = quasiquote(key, depth, env)?;
+        let value = quasiquote(value, depth, env)?;
+        expanded.insert(key, value);
     }
 
-    let sym = *funcall.elements[1].clone();
-    let value = *funcall.elements[2].clone();
+    Ok(Form::Map(Rc::new(expanded)))
+}
 
-    // env.lookup_symbol(sym)
-    let symref = match sym.clone() {
-        Form::Symbol(sym) => {
-            let r = env.lookup_symbol(sym);
-            Some(r)
-        }
-        _ => None,
+// Rebuilds a (name arg) form with its argument expanded at the given depth.
+fn requote(name: &str, arg: &Form, depth: usize, list: &List, env: &mut Environment) -> HanaResult {
+    let elements = vec![
+        Form::Symbol(name.to_string(), None),
+        quasiquote(arg, depth, env)?,
+    ];
+
+    Ok(Form::List(List::with_span(elements, list.span.clone())))
+}
+
+pub fn def_symbol(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "def", 2, Some(2))?;
+
+    let Form::Symbol(sym, _) = &args[0] else {
+        return Err(HanaError::type_mismatch("def", "a symbol", args[0].clone()));
     };
 
-    if let Some(symref) = symref {
-        // let value = symref.unwrap().borrow_mut().clone();
-
-        // *symref.clone().unwrap().borrow_mut() = Form::Symbol("test".to_string());
-        *symref.clone().unwrap().borrow_mut() = value;
-        // *value = Form::Nil();
-        println!("Value: {:?}", symref.unwrap().borrow_mut().clone());
-    } else {
-        println!(
-            "Error: cannot set value of a non-bound symbol ",
-            // sym.clone()
-        );
-    }
+    let evaluated = evaluate(args[1].clone(), env)?;
+    env.bind_symbol(sym.clone(), evaluated);
 
-    // let value = *funcall.elements[2].clone();
+    Ok(Form::Nil())
+}
 
-    // match sym {
-    //     Form::Symbol(sym) => {
-    //         let evaluated = evaluate(value, env);
-    //         env.bind_symbol(sym, evaluated);
-    //     }
-    //     _ => {}
-    // }
+/*
+    Overwrites the value of an already bound symbol, wherever in the
+    context-stack that binding lives.
+*/
+pub fn set_symbol(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "set", 2, Some(2))?;
 
-    return Some(Form::Nil());
+    let Form::Symbol(sym, _) = &args[0] else {
+        return Err(HanaError::type_mismatch("set", "a symbol", args[0].clone()));
+    };
+
+    let Some(symref) = env.lookup_symbol(sym.clone()) else {
+        return Err(HanaError::UnboundSymbol(sym.clone()));
+    };
+
+    let value = evaluate(args[1].clone(), env)?;
+    *symref.borrow_mut() = value;
+
+    Ok(Form::Nil())
 }
 
 /*
@@ -131,63 +239,70 @@ pub fn set_symbol(funcall: &List, env: &mut Environment) -> Option<Form> {
     returns the result of the last evaluated form. If no forms are given, it returns
     Nil.
 */
-pub fn handle_progn(funcall: &List, env: &mut Environment) -> Option<Form> {
-    let mut form = funcall.elements.iter();
-    form.next();
+pub fn handle_progn(funcall: &List, env: &mut Environment) -> Result<Tail, HanaError> {
+    let forms = expect_args(funcall, "progn", 0, None)?;
 
-    let mut eval = Form::Nil();
+    let Some((last, forms)) = forms.split_last() else {
+        return Ok(Tail::Return(Form::Nil()));
+    };
 
-    while let Some(form) = form.next() {
-        eval = evaluate(*form.clone(), env);
+    for form in forms {
+        evaluate(form.clone(), env)?;
     }
 
-    return Some(eval);
+    Ok(Tail::Eval(last.clone()))
 }
 
 /*
     A let special form takes a list of tuples (a list of two elems), and a form to evaluate.
     For each tuple in the list, the first element is expected to be a symbol, and the right value
     is bound to it in a new scope. Once all tuples are bound, the body form is evaluated.
+
+    The new scope is left as the current one for the body, and is closed by the
+    evaluator once it's done with the body.
 */
-pub fn handle_let(funcall: &List, env: &mut Environment) -> Option<Form> {
-    let mut itr = funcall.elements.iter();
-    itr.next();
-
-    let tuples = *itr.next().unwrap().clone();
-    let body = itr.next().unwrap();
-
-    match tuples {
-        Form::List(tuples) => {
-            // println!("Tuples list: {tuples:?}");
-            for tup in tuples.elements {
-                // println!("tup: {tup:?}");
-
-                match *tup {
-                    Form::List(tup) => {
-                        let sym = tup.elements.first().unwrap();
-                        let val = tup.elements.last().unwrap();
-
-                        if let Some(s) = match *sym.clone() {
-                            Form::Symbol(s) => Some(s),
-                            _ => None,
-                        } {
-                            let ev = evaluate(*val.clone(), env);
-                            // println!("sym: {s:?}, val: {val:?}");
-                            env.bind_symbol(s, ev);
-                        }
-                    }
-                    _ => {}
-                }
+pub fn handle_let(funcall: &List, env: &mut Environment) -> Result<Tail, HanaError> {
+    let args = expect_args(funcall, "let", 2, Some(2))?;
+
+    let Form::List(tuples) = &args[0] else {
+        return Err(HanaError::invalid_form(
+            "let",
+            "bindings must be a list of (symbol value) pairs",
+        ));
+    };
+
+    env.push_frame();
+    bind_let_tuples(tuples, env)?;
+
+    Ok(Tail::Eval(args[1].clone()))
+}
+
+// Binds each (symbol value) tuple of a let form in the current frame, in order.
+fn bind_let_tuples(tuples: &List, env: &mut Environment) -> Result<(), HanaError> {
+    for tup in tuples.elements() {
+        match tup {
+            Form::List(tup) if tup.elements().len() == 2 => {
+                let Form::Symbol(sym, _) = &tup.elements()[0] else {
+                    return Err(HanaError::type_mismatch(
+                        "let",
+                        "a symbol",
+                        tup.elements()[0].clone(),
+                    ));
+                };
+
+                let ev = evaluate(tup.elements()[1].clone(), env)?;
+                env.bind_symbol(sym.clone(), ev);
+            }
+            _ => {
+                return Err(HanaError::invalid_form(
+                    "let",
+                    &format!("binding {tup} is not a (symbol value) pair"),
+                ));
             }
-        }
-        _ => {
-            println!("Error:");
         }
     }
 
-    let e = evaluate(*body.clone(), env);
-
-    return Some(e);
+    Ok(())
 }
 
 /*
@@ -199,77 +314,45 @@ pub fn handle_let(funcall: &List, env: &mut Environment) -> Option<Form> {
     the symbol names given in a new context.
 
 */
-pub fn handle_each(funcall: &List, env: &mut Environment) -> Option<Form> {
-    let mut itr = funcall.elements.iter();
-    itr.next();
-
-    // if let some(key) = match *itr.next().unwrap().clone() {
-    //     form::symbol(_) => {}
-    //     _ => {}
-    // } {}
-    let mut eval: Form = Form::Nil();
-
-    let key = *itr.next().unwrap().clone();
-    let value = *itr.next().unwrap().clone();
-    let lst = *itr.next().unwrap().clone();
-    let body = *itr.next().unwrap().clone();
-
-    match (key.clone(), value.clone(), lst.clone(), body.clone()) {
-        (Form::Symbol(k), Form::Symbol(v), lst, _) => {
-            println!("{:?}", key);
-            println!("{:?}", value);
-
-            let myl: List = match lst.clone() {
-                Form::List(lst) => {
-                    println!("Not an error");
-                    lst
-                }
-                Form::Symbol(lst) => {
-                    println!("Not an error");
-                    // let l = lst;
-                    let symres = env
-                        .lookup_symbol(lst)
-                        .unwrap()
-                        .as_ref()
-                        .borrow_mut()
-                        .clone();
-                    // println!("{symres:?}");
-                    match symres {
-                        Form::List(symres) => symres,
-                        _ => List { elements: vec![] },
-                    }
-                }
-                _ => {
-                    println!("Error?");
-                    List { elements: vec![] }
-                }
-            };
-
-            env.push_new_context();
+pub fn handle_each(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "each", 4, Some(4))?;
+
+    let (Form::Symbol(k, _), Form::Symbol(v, _)) = (&args[0], &args[1]) else {
+        return Err(HanaError::invalid_form(
+            "each",
+            "the key and value names must be symbols",
+        ));
+    };
 
-            for elem in myl.elements.clone().into_iter() {
-                println!("v: {v:?}, elem: {elem:?}, body: {body:?}");
-                env.bind_symbol(v.clone(), *elem);
-                eval = evaluate(body.clone(), env);
-                println!("eval :{eval:?}");
+    let elements = match evaluate(args[2].clone(), env)? {
+        // iterates over a snapshot, so pushing to the vector in the body doesn't loop forever
+        Form::Vector(vector) => vector.borrow().to_vec(),
+        Form::Set(set) => set.iter().cloned().collect(),
+        seq => match seq.list_elements() {
+            Some(elements) => elements.into_owned(),
+            None => {
+                return Err(HanaError::type_mismatch(
+                    "each",
+                    "a list, vector or set",
+                    seq,
+                ))
             }
+        },
+    };
 
-            env.pop_context();
+    env.push_frame();
 
-            println!("myl: {:?}", myl);
-        }
-        // (Form::Symbol(key), Form::Symbol(value), Form::List(lst), _) => {
-        //     println!("{:?}", key);
-        //     println!("{:?}", value);
-        //     println!("{:?}", lst);
-        // }
-        _ => {
-            println!("Error? ");
+    let mut eval = Ok(Form::Nil());
+    for (i, elem) in elements.into_iter().enumerate() {
+        env.bind_symbol(k.clone(), Form::Integer(i as Integer));
+        env.bind_symbol(v.clone(), elem);
+        eval = evaluate(args[3].clone(), env);
+        if eval.is_err() {
+            break;
         }
     }
 
-    // return Some(e);
-    return Some(eval);
-}
+    env.pop_frame();
 
-fn each_get_kv(key: Form) {}
+    eval
+}
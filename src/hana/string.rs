@@ -0,0 +1,404 @@
+use crate::hana::builtin::{eval_index, expect_args, list_or_nil};
+use crate::hana::*;
+
+/*
+    The string and character library. Strings are indexed by character rather than by byte,
+    so every index and length here counts unicode scalar values, e.g.
+    (string-length "h\u{e9}llo") is 5.
+*/
+
+fn eval_str(name: &str, form: &Form, env: &mut Environment) -> Result<Str, HanaError> {
+    match evaluate(form.clone(), env)? {
+        Form::Str(s) => Ok(s),
+        other => Err(HanaError::type_mismatch(name, "a string", other)),
+    }
+}
+
+// Nil is accepted as an empty list of strings, like any other proper list.
+fn eval_strs(name: &str, form: &Form, env: &mut Environment) -> Result<Vec<Str>, HanaError> {
+    let list = evaluate(form.clone(), env)?;
+    let elements = match list.list_elements() {
+        Some(elements) => elements.into_owned(),
+        None => return Err(HanaError::type_mismatch(name, "a list of strings", list)),
+    };
+
+    elements
+        .into_iter()
+        .map(|elem| match elem {
+            Form::Str(s) => Ok(s),
+            other => Err(HanaError::type_mismatch(name, "a list of strings", other)),
+        })
+        .collect()
+}
+
+/*
+    Evaluates a character index into the string, and returns the byte offset it
+    points at. An index equal to the length points just past the end of the string.
+*/
+fn eval_char_index(
+    name: &str,
+    form: &Form,
+    s: &str,
+    env: &mut Environment,
+) -> Result<usize, HanaError> {
+    let index = eval_index(name, form, s.chars().count(), true, env)?;
+
+    Ok(s.char_indices().nth(index).map_or(s.len(), |(i, _)| i))
+}
+
+fn string_list(strs: impl Iterator<Item = impl Into<Str>>) -> Form {
+    list_or_nil(strs.map(|s| Form::Str(s.into())).collect())
+}
+
+/*
+    (str forms...)
+
+    Concatenates the forms into one string. Strings are added as they are, nil
+    adds nothing, and any other form is added the way print would write it.
+*/
+pub fn handle_str(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "str", 0, None)?;
+
+    let mut out = String::new();
+    for arg in args {
+        match evaluate(arg.clone(), env)? {
+            Form::Nil() => {}
+            form => out += &form.princ().to_string(),
+        }
+    }
+
+    Ok(Form::Str(Str::from(out)))
+}
+
+/*
+    (substring string start end?)
+
+    Returns the characters from start up to, but not including, end
+    (default: the end of the string).
+*/
+pub fn handle_substring(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "substring", 2, Some(3))?;
+
+    let s = eval_str("substring", &args[0], env)?;
+    let start = eval_char_index("substring", &args[1], &s, env)?;
+    let end = match args.get(2) {
+        Some(end) => eval_char_index("substring", end, &s, env)?,
+        None => s.len(),
+    };
+
+    if start > end {
+        return Err(HanaError::invalid_form(
+            "substring",
+            "the start index can't be past the end index",
+        ));
+    }
+
+    Ok(Form::Str(Str::from(&s[start..end])))
+}
+
+pub fn handle_string_length(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "string-length", 1, Some(1))?;
+
+    let s = eval_str("string-length", &args[0], env)?;
+
+    Ok(Form::Integer(s.chars().count() as Integer))
+}
+
+/*
+    (string-split string separator?)
+
+    Splits the string on every occurrence of the separator. With no separator
+    it's split on runs of whitespace, and an empty separator splits it into
+    single characters.
+*/
+pub fn handle_string_split(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "string-split", 1, Some(2))?;
+
+    let s = eval_str("string-split", &args[0], env)?;
+    let separator = match args.get(1) {
+        Some(sep) => Some(eval_str("string-split", sep, env)?),
+        None => None,
+    };
+
+    Ok(match separator.as_deref() {
+        None => string_list(s.split_whitespace()),
+        Some("") => string_list(s.chars().map(String::from)),
+        Some(sep) => string_list(s.split(sep)),
+    })
+}
+
+/*
+    (string-join strings separator?)
+
+    Joins a list of strings into one, with the separator (default: "")
+    between each of them.
+*/
+pub fn handle_string_join(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "string-join", 1, Some(2))?;
+
+    let strs = eval_strs("string-join", &args[0], env)?;
+    let separator = match args.get(1) {
+        Some(sep) => eval_str("string-join", sep, env)?,
+        None => Str::from(""),
+    };
+
+    Ok(Form::Str(Str::from(strs.join(&*separator))))
+}
+
+// Applies a function from one string to another to a single string argument.
+fn map_string(
+    funcall: &List,
+    env: &mut Environment,
+    name: &str,
+    op: fn(&str) -> String,
+) -> HanaResult {
+    let args = expect_args(funcall, name, 1, Some(1))?;
+
+    let s = eval_str(name, &args[0], env)?;
+
+    Ok(Form::Str(Str::from(op(&s))))
+}
+
+pub fn handle_string_trim(funcall: &List, env: &mut Environment) -> HanaResult {
+    map_string(funcall, env, "string-trim", |s| s.trim().to_string())
+}
+
+pub fn handle_upcase(funcall: &List, env: &mut Environment) -> HanaResult {
+    map_string(funcall, env, "upcase", str::to_uppercase)
+}
+
+pub fn handle_downcase(funcall: &List, env: &mut Environment) -> HanaResult {
+    map_string(funcall, env, "downcase", str::to_lowercase)
+}
+
+// Tests a string against a second 'pattern' string.
+fn test_string(
+    funcall: &List,
+    env: &mut Environment,
+    name: &str,
+    op: fn(&str, &str) -> bool,
+) -> HanaResult {
+    let args = expect_args(funcall, name, 2, Some(2))?;
+
+    let s = eval_str(name, &args[0], env)?;
+    let pattern = eval_str(name, &args[1], env)?;
+
+    Ok(Form::Bool(op(&s, &pattern)))
+}
+
+pub fn handle_starts_with(funcall: &List, env: &mut Environment) -> HanaResult {
+    test_string(funcall, env, "starts-with?", |s, prefix| {
+        s.starts_with(prefix)
+    })
+}
+
+pub fn handle_ends_with(funcall: &List, env: &mut Environment) -> HanaResult {
+    test_string(funcall, env, "ends-with?", |s, suffix| s.ends_with(suffix))
+}
+
+/*
+    (string-index string substring)
+
+    Returns the index of the first occurrence of the substring,
+    or nil if it doesn't occur.
+*/
+pub fn handle_string_index(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "string-index", 2, Some(2))?;
+
+    let s = eval_str("string-index", &args[0], env)?;
+    let substring = eval_str("string-index", &args[1], env)?;
+
+    Ok(match s.find(&*substring) {
+        Some(i) => Form::Integer(s[..i].chars().count() as Integer),
+        None => Form::Nil(),
+    })
+}
+
+/*
+    (string-replace string from to)
+
+    Replaces every occurrence of 'from' in the string with 'to'.
+*/
+pub fn handle_string_replace(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "string-replace", 3, Some(3))?;
+
+    let s = eval_str("string-replace", &args[0], env)?;
+    let from = eval_str("string-replace", &args[1], env)?;
+    let to = eval_str("string-replace", &args[2], env)?;
+
+    if from.is_empty() {
+        return Err(HanaError::invalid_form(
+            "string-replace",
+            "the string to replace can't be empty",
+        ));
+    }
+
+    Ok(Form::Str(Str::from(s.replace(&*from, &to))))
+}
+
+// (string->list string) splits a string into a list of its characters.
+pub fn handle_string_to_list(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "string->list", 1, Some(1))?;
+
+    let s = eval_str("string->list", &args[0], env)?;
+    Ok(list_or_nil(s.chars().map(Form::Char).collect()))
+}
+
+// (list->string chars) is the reverse of string->list, and also accepts strings in the list.
+pub fn handle_list_to_string(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "list->string", 1, Some(1))?;
+
+    let list = evaluate(args[0].clone(), env)?;
+    let elements = match list.list_elements() {
+        Some(elements) => elements.into_owned(),
+        None => return Err(HanaError::type_mismatch("list->string", "a list", list)),
+    };
+
+    let mut out = String::new();
+    for elem in elements {
+        match elem {
+            Form::Char(c) => out.push(c),
+            Form::Str(s) => out += &s,
+            other => {
+                return Err(HanaError::type_mismatch(
+                    "list->string",
+                    "a list of characters",
+                    other,
+                ))
+            }
+        }
+    }
+
+    Ok(Form::Str(Str::from(out)))
+}
+
+fn eval_char(name: &str, form: &Form, env: &mut Environment) -> Result<char, HanaError> {
+    match evaluate(form.clone(), env)? {
+        Form::Char(c) => Ok(c),
+        other => Err(HanaError::type_mismatch(name, "a character", other)),
+    }
+}
+
+pub fn handle_is_char(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "char?", 1, Some(1))?;
+
+    let form = evaluate(args[0].clone(), env)?;
+
+    Ok(Form::Bool(matches!(form, Form::Char(_))))
+}
+
+// Tests a single character argument.
+fn test_char(
+    funcall: &List,
+    env: &mut Environment,
+    name: &str,
+    op: fn(char) -> bool,
+) -> HanaResult {
+    let args = expect_args(funcall, name, 1, Some(1))?;
+
+    let c = eval_char(name, &args[0], env)?;
+
+    Ok(Form::Bool(op(c)))
+}
+
+pub fn handle_char_alphabetic(funcall: &List, env: &mut Environment) -> HanaResult {
+    test_char(funcall, env, "char-alphabetic?", char::is_alphabetic)
+}
+
+pub fn handle_char_whitespace(funcall: &List, env: &mut Environment) -> HanaResult {
+    test_char(funcall, env, "char-whitespace?", char::is_whitespace)
+}
+
+// (char->integer char) returns the character's unicode scalar value.
+pub fn handle_char_to_integer(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "char->integer", 1, Some(1))?;
+
+    let c = eval_char("char->integer", &args[0], env)?;
+
+    Ok(Form::Integer(c as Integer))
+}
+
+// (integer->char n) returns the character with the unicode scalar value n.
+pub fn handle_integer_to_char(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "integer->char", 1, Some(1))?;
+
+    let form = evaluate(args[0].clone(), env)?;
+    let c = match &form {
+        Form::Integer(i) => u32::try_from(*i).ok().and_then(char::from_u32),
+        _ => None,
+    };
+
+    c.map(Form::Char)
+        .ok_or_else(|| HanaError::type_mismatch("integer->char", "a unicode scalar value", form))
+}
+
+/*
+    (format control args...)
+
+    Builds a string out of the control string, replacing each directive in it:
+
+        ~a  the next arg, written the way print would (strings without quotes)
+        ~s  the next arg, written the way write would (strings with quotes)
+        ~%  a newline
+        ~~  a tilde
+
+    Every arg has to be used by exactly one directive.
+*/
+pub fn handle_format(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "format", 1, None)?;
+
+    let control = eval_str("format", &args[0], env)?;
+    let mut values = vec![];
+    for arg in &args[1..] {
+        values.push(evaluate(arg.clone(), env)?);
+    }
+
+    let mut values = values.into_iter();
+    let mut out = String::new();
+    let mut chars = control.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '~' {
+            out.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some(directive @ ('a' | 's')) => {
+                let Some(value) = values.next() else {
+                    return Err(HanaError::invalid_form(
+                        "format",
+                        &format!("'~{directive}' has no argument left to use"),
+                    ));
+                };
+                match directive {
+                    'a' => out += &value.princ().to_string(),
+                    _ => out += &value.to_string(),
+                }
+            }
+            Some('%') => out.push('\n'),
+            Some('~') => out.push('~'),
+            Some(other) => {
+                return Err(HanaError::invalid_form(
+                    "format",
+                    &format!("unknown directive '~{other}'"),
+                ))
+            }
+            None => {
+                return Err(HanaError::invalid_form(
+                    "format",
+                    "the control string can't end with a lone '~'",
+                ))
+            }
+        }
+    }
+
+    if values.next().is_some() {
+        return Err(HanaError::invalid_form(
+            "format",
+            "more arguments were given than the control string uses",
+        ));
+    }
+
+    Ok(Form::Str(Str::from(out)))
+}
@@ -0,0 +1,192 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+
+use crate::hana::builtin::{eval_index, expect_args};
+use crate::hana::*;
+
+/*
+    Vectors are growable arrays with constant time indexing. Unlike lists they're
+    mutable: a vector is shared between every form that holds it, so a change made
+    through vector-set! or vector-push! is seen everywhere the vector is.
+*/
+pub type Vector = Rc<RefCell<Contents<Vec<Form>>>>;
+
+pub fn new_vector(elements: Vec<Form>) -> Form {
+    Form::Vector(Rc::new(RefCell::new(Contents::from(elements))))
+}
+
+/*
+    Evaluates a vector literal, e.g. [1 (+ 1 1)], by evaluating each element in order.
+    Every evaluation creates a new vector, so changing one never changes the literal.
+*/
+pub fn evaluate_vector(vector: &Vector, env: &mut Environment) -> HanaResult {
+    let elements = vector.borrow().to_vec();
+
+    let mut evaluated = Vec::with_capacity(elements.len());
+    for elem in elements {
+        evaluated.push(evaluate(elem, env)?);
+    }
+
+    Ok(new_vector(evaluated))
+}
+
+fn eval_vector(name: &str, form: &Form, env: &mut Environment) -> Result<Vector, HanaError> {
+    match evaluate(form.clone(), env)? {
+        Form::Vector(vector) => Ok(vector),
+        other => Err(HanaError::type_mismatch(name, "a vector", other)),
+    }
+}
+
+// (vector elements...) returns a new vector holding the elements.
+pub fn handle_vector(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "vector", 0, None)?;
+
+    let mut elements = Vec::with_capacity(args.len());
+    for arg in args {
+        elements.push(evaluate(arg.clone(), env)?);
+    }
+
+    Ok(new_vector(elements))
+}
+
+/*
+    (vec collection)
+
+    Returns a new vector with the elements of a list, vector, set, or string (as characters).
+*/
+pub fn handle_vec(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "vec", 1, Some(1))?;
+
+    match evaluate(args[0].clone(), env)? {
+        Form::List(list) => Ok(new_vector(list.elements().to_vec())),
+        Form::Vector(vector) => Ok(new_vector(vector.borrow().to_vec())),
+        Form::Set(set) => Ok(new_vector(set.iter().cloned().collect())),
+        Form::Str(s) => Ok(new_vector(s.chars().map(Form::Char).collect())),
+        Form::Nil() => Ok(new_vector(vec![])),
+        other @ Form::Pair(_) => match other.list_elements() {
+            Some(elements) => Ok(new_vector(elements.into_owned())),
+            None => Err(HanaError::type_mismatch("vec", "a proper list", other)),
+        },
+        other => Err(HanaError::type_mismatch(
+            "vec",
+            "a list, vector, set or string",
+            other,
+        )),
+    }
+}
+
+// (vector-ref vector index) returns the element at the index.
+pub fn handle_vector_ref(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "vector-ref", 2, Some(2))?;
+
+    let vector = eval_vector("vector-ref", &args[0], env)?;
+    let length = vector.borrow().len();
+    let index = eval_index("vector-ref", &args[1], length, false, env)?;
+
+    let element = vector.borrow()[index].clone();
+
+    Ok(element)
+}
+
+/*
+    Whether the form is the vector, or holds it anywhere inside of it. Adding such a
+    form to the vector would make it contain itself, which nothing could then print,
+    compare or hash without going around forever.
+*/
+fn contains_vector(form: &Form, vector: &Vector) -> bool {
+    let mut seen = HashSet::new();
+    let mut pending = vec![form.clone()];
+    while let Some(form) = pending.pop() {
+        match form {
+            Form::Vector(v) => {
+                if Rc::ptr_eq(&v, vector) {
+                    return true;
+                }
+                if seen.insert(Rc::as_ptr(&v)) {
+                    pending.extend(v.borrow().iter().cloned());
+                }
+            }
+            Form::List(list) => pending.extend(list.elements().iter().cloned()),
+            Form::Pair(pair) => {
+                pending.push(pair.car.clone());
+                pending.push(pair.cdr.clone());
+            }
+            Form::Map(map) => {
+                pending.extend(map.iter().flat_map(|(k, v)| [k.clone(), v.clone()]));
+            }
+            Form::Set(set) => pending.extend(set.iter().cloned()),
+            _ => {}
+        }
+    }
+
+    false
+}
+
+fn check_not_self(name: &str, value: &Form, vector: &Vector) -> Result<(), HanaError> {
+    match contains_vector(value, vector) {
+        true => Err(HanaError::invalid_form(
+            name,
+            "a vector can't contain itself",
+        )),
+        false => Ok(()),
+    }
+}
+
+// (vector-set! vector index value) replaces the element at the index with the value.
+pub fn handle_vector_set(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "vector-set!", 3, Some(3))?;
+
+    let vector = eval_vector("vector-set!", &args[0], env)?;
+    let length = vector.borrow().len();
+    let index = eval_index("vector-set!", &args[1], length, false, env)?;
+    let value = evaluate(args[2].clone(), env)?;
+    check_not_self("vector-set!", &value, &vector)?;
+
+    // vectors never shrink, so the index is still in range after evaluating the value
+    vector.borrow_mut()[index] = value;
+
+    Ok(Form::Nil())
+}
+
+// (vector-push! vector values...) adds the values to the end of the vector.
+pub fn handle_vector_push(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "vector-push!", 2, None)?;
+
+    let vector = eval_vector("vector-push!", &args[0], env)?;
+    for arg in &args[1..] {
+        let value = evaluate(arg.clone(), env)?;
+        check_not_self("vector-push!", &value, &vector)?;
+        vector.borrow_mut().push(value);
+    }
+
+    Ok(Form::Nil())
+}
+
+/*
+    (subvec vector start end?)
+
+    Returns a new vector with the elements from start up to, but not including,
+    end (default: the end of the vector).
+*/
+pub fn handle_subvec(funcall: &List, env: &mut Environment) -> HanaResult {
+    let args = expect_args(funcall, "subvec", 2, Some(3))?;
+
+    let vector = eval_vector("subvec", &args[0], env)?;
+    let length = vector.borrow().len();
+    let start = eval_index("subvec", &args[1], length, true, env)?;
+    let end = match args.get(2) {
+        Some(end) => eval_index("subvec", end, length, true, env)?,
+        None => length,
+    };
+
+    if start > end {
+        return Err(HanaError::invalid_form(
+            "subvec",
+            "the start index can't be past the end index",
+        ));
+    }
+
+    let elements = vector.borrow()[start..end].to_vec();
+
+    Ok(new_vector(elements))
+}
@@ -1,100 +1,206 @@
-use std::ascii::AsciiExt;
-use std::collections::HashMap;
 use std::env;
-use std::ffi::CString;
 use std::fs;
+use std::io::{self, Read};
+use std::process;
+use std::thread;
 
 mod hana;
+mod repl;
+use hana::builtin::list_or_nil;
 use hana::*;
 
-fn main() {
-    env::set_var("RUST_BACKTRACE", "1");
-    let mut unparsed_file = fs::read_to_string("tests/bool.hana").expect("cannot read file!");
-
-    let mut funcs = HashMap::new();
-    funcs.insert(
-        "last",
-        "(def last (lambda (lst)
-                        (if (= (cdr lst) nil)
-                            (car lst)
-                            (last (cdr lst)))))",
-    );
-
-    let mut builtins = String::new();
-    for (_, f) in funcs {
-        builtins += f;
-        builtins += "\n";
-    }
+const USAGE: &str = "usage: hana [options] [script | -] [args...]
+
+With no script, hana starts an interactive REPL. Any arguments after the
+script are bound to *argv* as a list of strings, or nil if there are none.
+
+options:
+    -e, --eval <source>   evaluate the given source instead of a script
+    -p, --print-result    print the result of the last evaluated form
+    --max-depth <n>       how deeply evaluation may nest (default: 1000)
+    -h, --help            print this message";
+
+// Roughly how much of the Rust stack each level of nested evaluation may take up,
+// with plenty of headroom for unoptimized builds.
+const STACK_PER_LEVEL: usize = 16 * 1024;
+const MIN_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+// Functions written in Hana itself that are loaded into every new environment.
+const PRELUDE: &str = "
+(def last (lambda (lst)
+                (if (= (cdr lst) nil)
+                    (car lst)
+                    (last (cdr lst)))))
+";
 
-    unparsed_file = builtins + &unparsed_file;
+// Creates a global environment with the prelude already loaded.
+pub fn new_environment() -> Environment {
+    let mut env = Environment::new();
 
-    // let file = HanaParser::parse(Rule::program, &unparsed_file)
-    //     .expect("unsuccessful parse")
-    //     .next()
-    //     .unwrap();
+    for form in parse(PRELUDE).expect("the prelude should parse") {
+        evaluate(form, &mut env).expect("the prelude should evaluate");
+    }
 
-    let file = parse(&unparsed_file).expect("unsuccessful parse");
+    env
+}
 
-    // let context = Context {
-    //     bindings: HashMap::new(),
-    // };
+/*
+    Where the code to run comes from, as given on the command line.
+*/
+enum Input {
+    Repl,
+    Eval(String),
+    Stdin,
+    Script(String),
+}
 
-    let env = &mut Environment {
-        bindings: vec![Context::new()],
+struct Options {
+    input: Input,
+    print_result: bool,
+    max_depth: usize,
+    argv: Vec<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        input: Input::Repl,
+        print_result: false,
+        max_depth: DEFAULT_MAX_DEPTH,
+        argv: vec![],
     };
 
-    // env.bind_symbol("x".to_string(), Form::Integer(2));
-    // env.bind_symbol("y".to_string(), Form::Symbol("x".to_string()));
-
-    // (lambda (x) (+ 2 x))
-    // let f1 = Form::Function(Function {
-    //     params: vec![Form::Symbol("x".to_string())],
-    //     context: Context::new(),
-    //     body: Box::new(Form::List(List {
-    //         elements: vec![
-    //             Box::new(Form::Symbol("+".to_string())),
-    //             Box::new(Form::Integer(2)),
-    //             Box::new(Form::Symbol("x".to_string())),
-    //         ],
-    //     })),
-    // });
-
-    // (lambda () "Hello, hana!")
-    // let f2 = Form::Function(Function {
-    //     params: vec![],
-    //     context: Context::new(),
-    //     body: Box::new(Form::Str(CString::new("Hello, hana!").unwrap())),
-    // });
-
-    // (lambda () x)
-    // let f3 = Form::Function(Function {
-    //     params: vec![],
-    //     context: Context::new(),
-    //     body: Box::new(Form::Symbol("x".to_string())),
-    // });
-
-    // (lambda () (+ 2 x))
-    // let f4 = Form::Function(Function {
-    //     params: vec![],
-    //     context: Context::new(),
-    //     body: Box::new(Form::List(List {
-    //         elements: vec![
-    //             Box::new(Form::Symbol("+".to_string())),
-    //             Box::new(Form::Integer(2)),
-    //             Box::new(Form::Symbol("x".to_string())),
-    //         ],
-    //     })),
-    // });
-
-    // env.bind_symbol("f1".to_string(), f1);
-    // env.bind_symbol("f2".to_string(), f2);
-    // env.bind_symbol("f3".to_string(), f3);
-    // env.bind_symbol("f4".to_string(), f4);
-
-    let mut result = hana::Form::Nil();
-    for form in file {
-        result = hana::evaluate(form, env);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                process::exit(0);
+            }
+            "-p" | "--print-result" => options.print_result = true,
+            "--max-depth" => match args.next().map(|n| n.parse()) {
+                Some(Ok(n)) => options.max_depth = n,
+                _ => return Err(format!("'{arg}' expects a number")),
+            },
+            "-e" | "--eval" => match args.next() {
+                Some(source) => {
+                    options.input = Input::Eval(source);
+                    break;
+                }
+                None => return Err(format!("'{arg}' expects some source to evaluate")),
+            },
+            "-" => {
+                options.input = Input::Stdin;
+                break;
+            }
+            _ if arg.starts_with('-') => return Err(format!("unknown option '{arg}'")),
+            _ => {
+                options.input = Input::Script(arg);
+                break;
+            }
+        }
+    }
+
+    options.argv = args.collect();
+
+    Ok(options)
+}
+
+/*
+    Evaluates every form of a script in a fresh environment, stopping at the first
+    error. Returns the status code the process should exit with.
+*/
+fn run_script(name: &str, source: &str, options: &Options) -> i32 {
+    let mut env = new_environment();
+    env.set_max_depth(options.max_depth);
+
+    let argv = options
+        .argv
+        .iter()
+        .map(|arg| Form::Str(Str::from(arg.as_str())))
+        .collect();
+    env.bind_symbol("*argv*".to_string(), list_or_nil(argv));
+
+    let result = parse_file(name, source).and_then(|forms| {
+        let mut result = Form::Nil();
+        for form in forms {
+            result = evaluate(form, &mut env)?;
+        }
+        Ok(result)
+    });
+
+    match result {
+        Ok(result) => {
+            if options.print_result {
+                println!("{result}");
+            }
+            0
+        }
+        Err(HanaError::Exit(code)) => code,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            1
+        }
     }
+}
+
+fn run(options: Options) -> i32 {
+    match &options.input {
+        Input::Repl => match repl::run(options.max_depth) {
+            Ok(status) => status,
+            Err(e) => {
+                eprintln!("Error: {e}");
+                1
+            }
+        },
+        Input::Eval(source) => run_script("<eval>", source, &options),
+        Input::Stdin => {
+            let mut source = String::new();
+            match io::stdin().read_to_string(&mut source) {
+                Ok(_) => run_script("<stdin>", &source, &options),
+                Err(e) => {
+                    eprintln!("Error: could not read stdin: {e}");
+                    1
+                }
+            }
+        }
+        Input::Script(path) => match fs::read_to_string(path) {
+            Ok(source) => run_script(path, &source, &options),
+            Err(e) => {
+                eprintln!("Error: could not read '{path}': {e}");
+                1
+            }
+        },
+    }
+}
+
+fn main() {
+    env::set_var("RUST_BACKTRACE", "1");
+
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("Error: {e}\n\n{USAGE}");
+            process::exit(2);
+        }
+    };
+
+    // The interpreter runs on its own thread, with a stack big enough to
+    // reach the maximum depth before hitting a stack overflow error.
+    let stack_size = options
+        .max_depth
+        .saturating_mul(STACK_PER_LEVEL)
+        .max(MIN_STACK_SIZE);
+    let interpreter = thread::Builder::new()
+        .stack_size(stack_size)
+        .spawn(move || run(options));
+
+    let status = match interpreter.map(|handle| handle.join()) {
+        Ok(Ok(status)) => status,
+        Ok(Err(_)) => 101,
+        Err(e) => {
+            eprintln!("Error: could not start the interpreter: {e}");
+            1
+        }
+    };
 
-    println!("{:?}", result);
+    process::exit(status);
 }
@@ -0,0 +1,271 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+use crate::hana::*;
+use crate::new_environment;
+
+const PROMPT: &str = "hana> ";
+const CONTINUATION_PROMPT: &str = "  ... ";
+
+/*
+    The interactive read-eval-print loop. Each entry is read until its parentheses
+    balance, evaluated in an environment that persists between entries, and its
+    result printed. Errors are reported without leaving the loop.
+
+    Lines starting with ':' are REPL commands rather than Hana code:
+        :env          print every binding in the global context
+        :load <file>  evaluate every form in a file
+        :reset        throw away all bindings and start fresh
+        :quit         leave the REPL
+
+    Returns the status the REPL was left with, which is only non-zero
+    when Hana code calls (exit n).
+*/
+pub fn run(max_depth: usize) -> rustyline::Result<i32> {
+    let mut rl = DefaultEditor::new()?;
+    let history = history_path();
+    if let Some(history) = &history {
+        // there's no history on the first run, so a failed load is fine
+        let _ = rl.load_history(history);
+    }
+
+    let mut env = new_environment();
+    env.set_max_depth(max_depth);
+    let mut status = 0;
+
+    loop {
+        let input = match read_entry(&mut rl) {
+            Ok(input) => input,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e),
+        };
+
+        let input = input.trim();
+        if input.is_empty() {
+            continue;
+        }
+        rl.add_history_entry(input)?;
+
+        let exit = match input.strip_prefix(':') {
+            Some(command) => run_command(command, &mut env),
+            None => eval_source("<repl>", input, &mut env, true),
+        };
+
+        if let Some(code) = exit {
+            status = code;
+            break;
+        }
+    }
+
+    // losing the history isn't worth losing the status over, e.g. when $HOME isn't writable
+    if let Some(history) = &history {
+        if let Err(e) = rl.save_history(history) {
+            eprintln!(
+                "Warning: could not save history to '{}': {e}",
+                history.display()
+            );
+        }
+    }
+
+    Ok(status)
+}
+
+// Reads lines until the parentheses of the whole entry are balanced.
+fn read_entry(rl: &mut DefaultEditor) -> rustyline::Result<String> {
+    let mut input = rl.readline(PROMPT)?;
+
+    while !input.trim_start().starts_with(':') && paren_depth(&input) > 0 {
+        input.push('\n');
+        input += &rl.readline(CONTINUATION_PROMPT)?;
+    }
+
+    Ok(input)
+}
+
+/*
+    Counts how many parentheses (and brackets and braces) are left open in the source,
+    ignoring any that appear inside of a string, a comment or a character literal.
+*/
+fn paren_depth(source: &str) -> i32 {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut in_line_comment = false;
+    let mut block_comments = 0;
+    let mut escaped = false;
+    let mut chars = source.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' if in_line_comment => in_line_comment = false,
+            _ if in_line_comment => {}
+            '#' if !in_string && chars.peek() == Some(&'|') => {
+                chars.next();
+                block_comments += 1;
+            }
+            '|' if block_comments > 0 && chars.peek() == Some(&'#') => {
+                chars.next();
+                block_comments -= 1;
+            }
+            _ if block_comments > 0 => {}
+            // a character literal like #\( is never a paren
+            '#' if !in_string && chars.peek() == Some(&'\\') => {
+                chars.next();
+                chars.next();
+            }
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            ';' if !in_string => in_line_comment = true,
+            '(' | '[' | '{' if !in_string => depth += 1,
+            ')' | ']' | '}' if !in_string => depth -= 1,
+            _ => {}
+        }
+    }
+
+    // an unterminated block comment needs more input, just like an open list
+    depth + block_comments
+}
+
+// Returns the exit status when the REPL should exit.
+fn run_command(command: &str, env: &mut Environment) -> Option<i32> {
+    let (name, arg) = match command.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (command, ""),
+    };
+
+    match name {
+        "env" => {
+            let global = env.global_frame();
+            let ctx = global.bindings.borrow();
+            let mut names: Vec<&Symbol> = ctx.keys().collect();
+            names.sort();
+            for name in names {
+                println!("{name} = {}", ctx[name].borrow());
+            }
+        }
+        "load" => match fs::read_to_string(arg) {
+            Ok(source) => return eval_source(arg, &source, env, false),
+            Err(e) => eprintln!("Error: could not read '{arg}': {e}"),
+        },
+        "reset" => {
+            let max_depth = env.max_depth;
+            *env = new_environment();
+            env.set_max_depth(max_depth);
+        }
+        "quit" | "q" => return Some(0),
+        _ => eprintln!("Error: unknown command ':{name}'"),
+    }
+
+    None
+}
+
+/*
+    Evaluates every form in the source, stopping at the first error.
+    When 'print' is set, the result of each form is printed.
+    Returns the exit status if one of the forms called (exit n).
+*/
+fn eval_source(name: &str, source: &str, env: &mut Environment, print: bool) -> Option<i32> {
+    let forms = match parse_file(name, source) {
+        Ok(forms) => forms,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            return None;
+        }
+    };
+
+    for form in forms {
+        match evaluate(form, env) {
+            Ok(result) if print => println!("{result}"),
+            Ok(_) => {}
+            Err(HanaError::Exit(code)) => return Some(code),
+            Err(e) => {
+                eprintln!("Error: {e}");
+                return None;
+            }
+        }
+    }
+
+    None
+}
+
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".hana_history"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(source: &str, env: &mut Environment) -> HanaResult {
+        let forms = parse_file("<test>", source)?;
+        let mut result = Form::Nil();
+        for form in forms {
+            result = evaluate(form, env)?;
+        }
+        Ok(result)
+    }
+
+    #[test]
+    fn paren_depth_counts_open_lists() {
+        assert_eq!(paren_depth(""), 0);
+        assert_eq!(paren_depth("(+ 1 2)"), 0);
+        assert_eq!(paren_depth("(def x [1 {:a"), 3);
+        assert_eq!(paren_depth("(list 1))"), -1);
+    }
+
+    #[test]
+    fn paren_depth_skips_strings_chars_and_comments() {
+        assert_eq!(paren_depth(r#"(print "(((")"#), 0);
+        assert_eq!(paren_depth(r#"(print "a \"(")"#), 0);
+        assert_eq!(paren_depth(r#"(print "unterminated ("#), 1);
+        assert_eq!(paren_depth(r"(list #\( #\[ #\))"), 0);
+        assert_eq!(paren_depth(r"(list #\)"), 1);
+        assert_eq!(paren_depth("(+ 1 ; (((\n 2)"), 0);
+        assert_eq!(paren_depth("(+ 1 #| ( |# 2)"), 0);
+        assert_eq!(paren_depth("#| #| |# ( |# (+ 1"), 1);
+    }
+
+    #[test]
+    fn quit_and_unknown_commands() {
+        let mut env = new_environment();
+
+        assert_eq!(run_command("quit", &mut env), Some(0));
+        assert_eq!(run_command("q", &mut env), Some(0));
+        assert_eq!(run_command("nonsense", &mut env), None);
+        assert_eq!(run_command("env", &mut env), None);
+    }
+
+    #[test]
+    fn reset_drops_bindings_but_keeps_max_depth() {
+        let mut env = new_environment();
+        env.set_max_depth(50);
+        eval("(def x 1)", &mut env).unwrap();
+
+        assert_eq!(run_command("reset", &mut env), None);
+        assert!(eval("x", &mut env).is_err());
+        assert_eq!(env.max_depth, 50);
+    }
+
+    #[test]
+    fn load_evaluates_a_file() {
+        let path = env::temp_dir().join(format!("hana-repl-load-{}.hana", std::process::id()));
+        fs::write(&path, "(def x 1)\n(def y (+ x 1))").unwrap();
+
+        let mut env = new_environment();
+        let command = format!("load {}", path.display());
+        assert_eq!(run_command(&command, &mut env), None);
+        assert_eq!(eval("y", &mut env).unwrap(), Form::Integer(2));
+
+        fs::write(&path, "(def z 3) (exit 4) (def z 5)").unwrap();
+        assert_eq!(run_command(&command, &mut env), Some(4));
+        assert_eq!(eval("z", &mut env).unwrap(), Form::Integer(3));
+
+        fs::remove_file(&path).unwrap();
+        assert_eq!(run_command(&command, &mut env), None);
+    }
+}
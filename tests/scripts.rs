@@ -0,0 +1,318 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+/*
+    Every script in tests/scripts is a self-checking Hana program: it calls 'check'
+    for each thing it tests, exits with status 1 on the first failure, and prints
+    "ok" once everything has passed.
+*/
+const CHECK: &str =
+    r#"(def check (lambda (name ok) (if ok nil (progn (println "FAIL:" name) (exit 1)))))"#;
+
+// Runs hana with the given arguments, feeding it the source on stdin.
+fn run_hana(args: &[&str], source: &str) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_hana"));
+    command.args(args);
+    run_command(command, source)
+}
+
+fn run_command(mut command: Command, source: &str) -> Output {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("hana should start");
+
+    child
+        .stdin
+        .take()
+        .expect("stdin should be piped")
+        .write_all(source.as_bytes())
+        .expect("the source should be written to stdin");
+
+    child.wait_with_output().expect("hana should finish")
+}
+
+#[test]
+fn scripts() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/scripts");
+    let mut paths: Vec<_> = fs::read_dir(&dir)
+        .expect("tests/scripts should exist")
+        .map(|entry| entry.expect("tests/scripts should be readable").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "hana"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no scripts found in {}", dir.display());
+
+    let mut failures = vec![];
+    for path in &paths {
+        let script = fs::read_to_string(path).expect("the script should be readable");
+
+        // 'check' goes on the script's first line, so errors still point at the right line
+        let output = run_hana(&["-"], &format!("{CHECK} {script}"));
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if !output.status.success() || stdout.trim() != "ok" {
+            failures.push(format!(
+                "{}: {}\n{}{}",
+                path.display(),
+                output.status,
+                stdout,
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+    }
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+// Runs the source and checks that it fails with the given status and error message.
+fn expect_error(args: &[&str], source: &str, status: i32, message: &str) -> String {
+    let output = run_hana(args, source);
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+
+    assert_eq!(output.status.code(), Some(status), "{source}\n{stderr}");
+    assert!(stderr.contains(message), "{source}\n{stderr}");
+
+    stderr
+}
+
+#[test]
+fn vectors_cant_contain_themselves() {
+    for source in [
+        "(def v [1]) (vector-push! v v) v",
+        "(def v [1]) (vector-set! v 0 {:a (list [v])}) v",
+        "(def v [1]) (def w [v]) (vector-push! v w) v",
+    ] {
+        expect_error(&["-p", "-"], source, 1, "a vector can't contain itself");
+    }
+}
+
+#[test]
+fn unbound_symbols_are_located() {
+    let stderr = expect_error(
+        &["-"],
+        "(def f (lambda () y))\n(f)",
+        1,
+        "symbol 'y' is not bound",
+    );
+    assert!(stderr.contains("--> <stdin>:1:19"), "{stderr}");
+
+    let stderr = expect_error(&["-"], "1\nx", 1, "symbol 'x' is not bound");
+    assert!(stderr.contains("--> <stdin>:2:1"), "{stderr}");
+}
+
+#[test]
+fn runaway_recursion_is_an_error() {
+    let stderr = expect_error(
+        &["-"],
+        "(def f (lambda (n) (+ 1 (f (- n 1))))) (f 100000)",
+        1,
+        "stack overflow",
+    );
+    assert!(stderr.contains("call stack"), "{stderr}");
+    assert!(stderr.contains("    f\n"), "{stderr}");
+}
+
+#[test]
+fn max_depth_raises_the_limit() {
+    let source = "(def f (lambda (n) (if (<= n 0) 0 (+ 1 (f (- n 1)))))) (f 2000)";
+    expect_error(&["-p", "-"], source, 1, "stack overflow");
+
+    let output = run_hana(&["--max-depth", "10000", "-p", "-"], source);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "2000");
+}
+
+#[test]
+fn runaway_macro_expansion_is_an_error() {
+    let defmacro = "(defmacro m (x) (list (quote m) x))\n";
+    for call in ["(m 1)", "(macroexpand '(m 1))"] {
+        let stderr = expect_error(&["-"], &format!("{defmacro}{call}"), 1, "stack overflow");
+        assert!(stderr.contains("--> <stdin>:2:1"), "{stderr}");
+    }
+}
+
+#[test]
+fn indices_past_the_integer_range() {
+    for (source, message) in [
+        (
+            "(substring \"abc\" 99999999999)",
+            "'substring' index 99999999999 is out of range for length 3",
+        ),
+        (
+            "(vector-ref [1] 99999999999)",
+            "'vector-ref' index 99999999999 is out of range for length 1",
+        ),
+        (
+            "(exit 99999999999)",
+            "status code 99999999999 is out of range 0-255",
+        ),
+    ] {
+        expect_error(&["-"], source, 1, message);
+    }
+}
+
+#[test]
+fn exit_passes_its_status_to_the_host() {
+    for code in [0, 3, 255] {
+        let output = run_hana(&["-"], &format!("(exit {code}) (exit 1)"));
+        assert_eq!(output.status.code(), Some(code), "{output:?}");
+    }
+
+    for code in ["256", "300", "-1"] {
+        expect_error(
+            &["-"],
+            &format!("(exit {code})"),
+            1,
+            &format!("status code {code} is out of range 0-255"),
+        );
+    }
+    expect_error(&["-"], "(exit \"3\")", 1, "'exit' expected an integer");
+}
+
+#[test]
+fn argv_holds_the_script_arguments() {
+    let output = run_hana(&["-p", "-"], "*argv*");
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "nil");
+
+    let output = run_hana(&["-p", "-", "a", "b c"], "*argv*");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        "(\"a\" \"b c\")"
+    );
+}
+
+#[test]
+fn malformed_numbers_are_read_errors() {
+    for literal in ["#b102", "#x", "#o9", "1/0", "-3/0"] {
+        let stderr = expect_error(
+            &["-"],
+            &format!("(list 1\n  {literal})"),
+            1,
+            &format!("'{literal}' is not a valid number"),
+        );
+        assert!(stderr.contains("--> <stdin>:2:3"), "{stderr}");
+    }
+}
+
+#[test]
+fn keyword_names_must_read_back() {
+    for name in ["a b", "", "a(", "x;y", "λ"] {
+        expect_error(
+            &["-"],
+            &format!("(keyword \"{name}\")"),
+            1,
+            &format!("\"{name}\" is not a valid keyword name"),
+        );
+    }
+}
+
+#[test]
+fn set_only_changes_bindings() {
+    expect_error(
+        &["-"],
+        "(set)",
+        1,
+        "'set' takes 2 argument(s), but was given 0",
+    );
+    expect_error(
+        &["-"],
+        "(set '(1 2))",
+        1,
+        "'set' takes 2 argument(s), but was given 1",
+    );
+    expect_error(&["-"], "(set 1 2)", 1, "'set' expected a symbol, found 1");
+}
+
+#[test]
+fn repl_keeps_the_exit_status_without_history() {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_hana"));
+    command.env("HOME", "/nonexistent");
+
+    let output = run_command(command, "(exit 3)\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(3), "{stderr}");
+    assert!(stderr.contains("could not save history"), "{stderr}");
+}
+
+#[test]
+fn deeply_nested_forms_are_read_errors() {
+    // the quote counts as a level of nesting too
+    let nested = |depth: usize| format!("'{}1{}", "(".repeat(depth - 1), ")".repeat(depth - 1));
+    let output = run_hana(&["-p", "-"], &nested(256));
+    assert!(output.status.success(), "{output:?}");
+
+    for source in [
+        nested(257),
+        format!("(list\n  {})", nested(300)),
+        format!("(list\n  {}1", "'".repeat(100_000)),
+        format!("(list\n  {}", "[#{".repeat(100_000)),
+    ] {
+        let stderr = expect_error(
+            &["-"],
+            &source,
+            1,
+            "forms can't be nested more than 256 deep",
+        );
+        assert!(stderr.contains("--> <stdin>:"), "{stderr}");
+    }
+}
+
+/*
+    Values nested 100000 deep, built up inside of each kind of collection. Walking
+    these by recursing overflows the stack, which aborts hana instead of raising
+    an error, so dropping, comparing, printing and hashing them mustn't recurse.
+*/
+const NEST: &str = r#"
+(def nest (lambda (n wrap acc) (if (= n 0) acc (nest (- n 1) wrap (wrap acc)))))
+(def wraps (list (lambda (x) (list x))
+                 (lambda (x) (cons x nil))
+                 (lambda (x) (cons 1 x))
+                 (lambda (x) (vector x))
+                 (lambda (x) {:k x})
+                 (lambda (x) (hash-set x))))
+"#;
+
+// Runs the body once for each kind of nested value, with 'wrap' bound to the function that builds it.
+fn check_nested_values(body: &str) {
+    let source = format!("{CHECK} {NEST} (each i wrap wraps {body}) (print \"ok\")");
+    let output = run_hana(&["-"], &source);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{body}\n{stdout}{stderr}");
+    assert_eq!(stdout.trim(), "ok", "{body}\n{stderr}");
+}
+
+#[test]
+fn deeply_nested_values_are_dropped() {
+    check_nested_values("(progn (def x (nest 100000 wrap nil)) (def x nil))");
+}
+
+#[test]
+fn deeply_nested_values_are_compared() {
+    check_nested_values(
+        "(let ((x (nest 100000 wrap 1)) (y (nest 100000 wrap 1)))
+           (progn (check \"equal nested values\" (= x y))
+                  (check \"unequal nested values\" (= (= x (wrap y)) false))))",
+    );
+}
+
+#[test]
+fn deeply_nested_values_are_printed() {
+    check_nested_values(
+        "(let ((x (nest 100000 wrap 1)))
+           (check \"printing nested values\" (< 200000 (string-length (str x)))))",
+    );
+}
+
+#[test]
+fn deeply_nested_values_are_hashed() {
+    check_nested_values(
+        "(let ((x (nest 100000 wrap 1)) (y (nest 100000 wrap 1)))
+           (progn (check \"hashing nested values\" (= (len (keys {x 1 y 2})) 1))
+                  (check \"hashing nested values\" (contains? (hash-set x) y))))",
+    );
+}